@@ -1,70 +1,21 @@
 use std::{
-    fs::File,
-    io::{self, BufReader, Read, Write},
+    io::{self, Write},
     time::Instant,
 };
 
-use rand::seq::SliceRandom;
-
-use neural::{matrix::init, nn::NeuralNetwork};
-
-macro_rules! verify_img_header {
-    ($n:expr, $buf:expr) => {
-        assert_eq!(
-            (($buf[0] as i32) << 24)
-                + (($buf[1] as i32) << 16)
-                + (($buf[2] as i32) << 8)
-                + ($buf[3] as i32),
-            0x00000803
-        );
-        assert_eq!(
-            (($buf[4] as i32) << 24)
-                + (($buf[5] as i32) << 16)
-                + (($buf[6] as i32) << 8)
-                + ($buf[7] as i32),
-            $n
-        );
-        assert_eq!(
-            (($buf[8] as i32) << 24)
-                + (($buf[9] as i32) << 16)
-                + (($buf[10] as i32) << 8)
-                + ($buf[11] as i32),
-            28
-        );
-        assert_eq!(
-            (($buf[12] as i32) << 24)
-                + (($buf[13] as i32) << 16)
-                + (($buf[14] as i32) << 8)
-                + ($buf[15] as i32),
-            28
-        );
-    };
-}
-
-macro_rules! verify_labels_header {
-    ($n:expr, $buf:expr) => {
-        assert_eq!(
-            (($buf[0] as i32) << 24)
-                + (($buf[1] as i32) << 16)
-                + (($buf[2] as i32) << 8)
-                + ($buf[3] as i32),
-            0x00000801
-        );
-        assert_eq!(
-            (($buf[4] as i32) << 24)
-                + (($buf[5] as i32) << 16)
-                + (($buf[6] as i32) << 8)
-                + ($buf[7] as i32),
-            $n
-        );
-    };
-}
-
-#[derive(Copy, Clone)]
-struct Image {
-    pub data: [f32; 784],
-    pub label: [f32; 10],
-}
+use rand::Rng;
+
+use neural::{
+    checkpoint::{CheckpointManager, TrainingState},
+    data::{
+        augment::{Augmenter, ImageShape, Rotate, Translate},
+        mnist::{MnistLoader, Split},
+        Dataset,
+    },
+    matrix::init,
+    metrics::Metric,
+    nn::NeuralNetwork,
+};
 
 #[inline(always)]
 fn secs_to_human(secs: u64) -> String {
@@ -84,140 +35,67 @@ fn secs_to_human(secs: u64) -> String {
     s
 }
 
-fn parse_training_images() -> Vec<Image> {
-    let mut images = Vec::new();
-    let img_f = File::open("./data/mnist/train-images-idx3-ubyte").unwrap();
-    let label_f = File::open("./data/mnist/train-labels-idx1-ubyte").unwrap();
-    let mut img_reader = BufReader::new(img_f);
-    let mut label_reader = BufReader::new(label_f);
-
-    let mut img_header_buf: [u8; 16] = [0; 16];
-    img_reader.read_exact(&mut img_header_buf).unwrap();
-    verify_img_header!(60000, img_header_buf);
-
-    let mut label_header_buf: [u8; 8] = [0; 8];
-    label_reader.read_exact(&mut label_header_buf).unwrap();
-    verify_labels_header!(60000, label_header_buf);
-
-    for _ in 0..60000 {
-        let mut img_buf: [u8; 784] = [0; 784];
-        let mut label_buf: [u8; 1] = [0; 1];
-        img_reader.read_exact(&mut img_buf).unwrap();
-        label_reader.read_exact(&mut label_buf).unwrap();
-        assert!(label_buf[0] < 10);
-        let mut label = [0.0; 10];
-        label[label_buf[0] as usize] = 1.0;
-        images.push(Image {
-            data: img_buf
-                .iter()
-                .map(|v| *v as f32 / 255.0)
-                .collect::<Vec<f32>>()
-                .try_into()
-                .unwrap(),
-            label,
-        });
-    }
-
-    images
-}
-
-fn parse_test_images() -> Vec<Image> {
-    let mut images = Vec::new();
-    let img_f = File::open("./data/mnist/t10k-images-idx3-ubyte").unwrap();
-    let label_f = File::open("./data/mnist/t10k-labels-idx1-ubyte").unwrap();
-    let mut img_reader = BufReader::new(img_f);
-    let mut label_reader = BufReader::new(label_f);
-
-    let mut img_header_buf: [u8; 16] = [0; 16];
-    img_reader.read_exact(&mut img_header_buf).unwrap();
-    verify_img_header!(10000, img_header_buf);
-
-    let mut label_header_buf: [u8; 8] = [0; 8];
-    label_reader.read_exact(&mut label_header_buf).unwrap();
-    verify_labels_header!(10000, label_header_buf);
-
-    for _ in 0..10000 {
-        let mut img_buf: [u8; 784] = [0; 784];
-        let mut label_buf: [u8; 1] = [0; 1];
-        img_reader.read_exact(&mut img_buf).unwrap();
-        label_reader.read_exact(&mut label_buf).unwrap();
-        assert!(label_buf[0] < 10);
-        let mut label = [0.0; 10];
-        label[label_buf[0] as usize] = 1.0;
-        images.push(Image {
-            data: img_buf
-                .iter()
-                .map(|v| *v as f32 / 255.0)
-                .collect::<Vec<f32>>()
-                .try_into()
-                .unwrap(),
-            label,
-        });
-    }
-
-    images
-}
-
 fn main() {
     init();
 
-    let training = parse_training_images();
-    let tests = parse_test_images();
+    let loader = MnistLoader::new("./data/mnist");
+    let training = loader.load(Split::Train).unwrap();
+    let tests = loader.load(Split::Test).unwrap();
 
-    let mut nn = NeuralNetwork::new(784, vec![16, 16, 16], 10);
-    nn.set_learning_rate(0.04);
+    let checkpoints = CheckpointManager::new("./checkpoints/mnist", 3).unwrap();
+    let (mut nn, mut state) = match checkpoints.resume().unwrap() {
+        Some((nn, state)) => {
+            println!("Resuming from step {}", state.step);
+            (nn, state)
+        }
+        None => {
+            let mut nn = NeuralNetwork::new(784, vec![16, 16, 16], 10);
+            nn.set_learning_rate(0.04);
+            (nn, TrainingState::new(0))
+        }
+    };
 
     let mut before = 100.0;
     if true {
         println!("Testing...");
 
-        let mut correct = 0;
-        let mut total = 0;
-        for test_img in &tests {
-            let pred = nn.feedforward(test_img.data.to_vec());
-            let label_index = 'out: {
-                for (i, l) in test_img.label.iter().enumerate() {
-                    if *l == 1.0 {
-                        break 'out i;
-                    }
-                }
-                unreachable!();
-            };
-            let max = {
-                let mut m = 0.0;
-                let mut idx = 0;
-                for (i, p) in pred.iter().enumerate() {
-                    if *p > m {
-                        idx = i;
-                        m = *p;
-                    }
-                }
-                idx
-            };
-            if label_index == max {
-                correct += 1;
-            }
-            total += 1;
-        }
-        before = correct as f64 / total as f64 * 100.0;
+        let report = nn.evaluate(&tests, &[Metric::Accuracy]);
+        before = report.accuracy.unwrap() as f64 * 100.0;
         println!("Accuracy: {:.20}%", before);
     }
 
     println!(
         "pred: {:?} actual: {:?}",
-        nn.feedforward(tests[0].data.to_vec()),
-        tests[0].label
+        nn.feedforward(tests.input(0).to_vec()),
+        tests.target(0)
     );
 
+    let augmenter = Augmenter::new(ImageShape::new(1, 28, 28), 0)
+        .then(Rotate { max_degrees: 10.0 })
+        .then(Translate {
+            max_dx: 2,
+            max_dy: 2,
+        });
+
     let start = Instant::now();
     let mut last = 0;
-    const TRAINING_ITERATIONS: usize = 1000000;
-    print!("Training... Elapsed time: 0s [0/{TRAINING_ITERATIONS} 0.00%]");
+    const TRAINING_ITERATIONS: u64 = 1000000;
+    const CHECKPOINT_INTERVAL: u64 = 50000;
+    print!(
+        "Training... Elapsed time: 0s [{}/{TRAINING_ITERATIONS}]",
+        state.step
+    );
     io::stdout().flush().unwrap();
-    let mut rng = rand::thread_rng();
-    for index in 0..TRAINING_ITERATIONS {
-        let training_img = training.choose(&mut rng).unwrap();
-        nn.train(&training_img.data, &training_img.label);
+    while state.step < TRAINING_ITERATIONS {
+        let sample = state.rng.gen_range(0..training.len());
+        let mut image = training.input(sample).to_vec();
+        augmenter.apply(&mut image, &mut state.rng);
+        nn.train(&image, training.target(sample));
+        state.step += 1;
+        if state.step % CHECKPOINT_INTERVAL == 0 {
+            checkpoints.save(&nn, &state).unwrap();
+        }
+        let index = state.step;
         let elapsed_secs = start.elapsed().as_secs();
         if elapsed_secs - last > 0 {
             print!(
@@ -237,41 +115,15 @@ fn main() {
 
     println!("Testing...");
 
-    let mut correct = 0;
-    let mut total = 0;
-    for test_img in &tests {
-        let pred = nn.feedforward(test_img.data.to_vec());
-        let label_index = 'out: {
-            for (i, l) in test_img.label.iter().enumerate() {
-                if *l == 1.0 {
-                    break 'out i;
-                }
-            }
-            unreachable!();
-        };
-        let max = {
-            let mut m = 0.0;
-            let mut idx = 0;
-            for (i, p) in pred.iter().enumerate() {
-                if *p > m {
-                    idx = i;
-                    m = *p;
-                }
-            }
-            idx
-        };
-        if label_index == max {
-            correct += 1;
-        }
-        total += 1;
-    }
+    let report = nn.evaluate(&tests, &[Metric::Classification]);
 
-    let after = correct as f64 / total as f64 * 100.0;
+    let after = report.classification.as_ref().unwrap().accuracy as f64 * 100.0;
     println!("Accuracy: {:.20}% +{}%", after, after - before);
+    println!("{report}");
 
     println!(
         "pred: {:?} actual: {:?}",
-        nn.feedforward(tests[0].data.to_vec()),
-        tests[0].label
+        nn.feedforward(tests.input(0).to_vec()),
+        tests.target(0)
     );
 }
@@ -1,6 +1,42 @@
-use neural::matrix::{init, Matrix};
+use neural::{
+    checkpoint::{self, CheckpointManager, TrainingState},
+    data::{
+        augment::{
+            Augmenter, Elastic, GaussianNoise, HorizontalFlip, ImageShape, RandomErasing, Rotate,
+            Scale, Translate,
+        },
+        cifar::{self, CifarLoader},
+        csv::{self, CsvLoader, MissingValues},
+        idx::{self, Data, IdxArray},
+        libsvm::{self, LibsvmLoader, Targets},
+        mnist::{self, EmnistSplit, MnistLoader, Split, Variant},
+        split::{self, KFold, Subset},
+        synthetic, Batch, DataLoader, Dataset, InMemoryDataset,
+    },
+    format::{binary, npy, onnx},
+    labels::{self, LabelEncoder},
+    matrix::{init, Matrix},
+    metrics::{
+        self, Average, ConfusionMatrix, Metric, PrecisionRecallCurve, RegressionReport, Report,
+        RocCurve,
+    },
+    nn::{Activation, Layer, NeuralNetwork},
+    preprocessing::{
+        self, MinMaxScaler, Norm, Normalizer, Pipeline, RobustScaler, Scaler, StandardScaler,
+        Transformer,
+    },
+};
 use rand::Rng;
 
+/// A layer of `rows` neurons with `columns` inputs and random parameters.
+fn layer(rows: usize, columns: usize, activation: Activation) -> Layer {
+    let mut weights = Matrix::new(rows, columns);
+    weights.randomize();
+    let mut bias = Matrix::new(rows, 1);
+    bias.randomize();
+    Layer::new(weights, bias, activation)
+}
+
 fn mat_mult_mat() {
     {
         let rows = 3;
@@ -41,8 +77,8 @@ fn mat_mult_scal() {
         let a_dat = vec![1.5, 1.0, 2.5, 2.5, 2.0, 3.5, 3.5, 3.0, 4.5];
 
         let mut c_dat = Vec::new();
-        for i in 0..a_dat.len() {
-            c_dat.push(a_dat[i] * scal);
+        for a in &a_dat {
+            c_dat.push(a * scal);
         }
         let mut a = Matrix::from_slice_cm(&a_dat, rows, cols);
         a.multiply_scalar(scal);
@@ -54,8 +90,8 @@ fn mat_mult_scal() {
         let scal = 0.023;
         let a_dat = vec![30.0; rows * cols];
         let mut c_dat = Vec::new();
-        for i in 0..a_dat.len() {
-            c_dat.push(a_dat[i] * scal);
+        for a in &a_dat {
+            c_dat.push(a * scal);
         }
         let mut a = Matrix::from_slice_cm(&a_dat, rows, cols);
         a.multiply_scalar(scal);
@@ -140,6 +176,25 @@ fn mat_add_mat() {
     println!("\x1b[0;32mpassed\x1b[0m");
 }
 
+fn mat_add_scaled_mat() {
+    {
+        let rows = 3;
+        let cols = 2;
+        let alpha = -0.5;
+        let a_dat = vec![1.5, 1.0, 2.5, 2.5, 2.0, 3.5];
+        let b_dat = vec![2.0, 4.0, 6.0, 8.0, 10.0, 12.0];
+        let mut c_dat = Vec::new();
+        for i in 0..a_dat.len() {
+            c_dat.push(a_dat[i] + alpha * b_dat[i]);
+        }
+        let mut a = Matrix::from_slice_cm(&a_dat, rows, cols);
+        let b = Matrix::from_slice_cm(&b_dat, rows, cols);
+        a.add_scaled_matrix(&b, alpha);
+        assert_eq!(a.to_vec(), c_dat);
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
 fn mat_add_scal() {
     {
         let rows = 3;
@@ -148,8 +203,8 @@ fn mat_add_scal() {
         let a_dat = vec![1.5, 1.0, 2.5, 2.5, 2.0, 3.5, 3.5, 3.0, 4.5];
 
         let mut c_dat = Vec::new();
-        for i in 0..a_dat.len() {
-            c_dat.push(a_dat[i] + scal);
+        for a in &a_dat {
+            c_dat.push(a + scal);
         }
         let mut a = Matrix::from_slice_cm(&a_dat, rows, cols);
         a.add_scalar(scal);
@@ -161,8 +216,8 @@ fn mat_add_scal() {
         let scal = 0.023;
         let a_dat = vec![30.0; rows * cols];
         let mut c_dat = Vec::new();
-        for i in 0..a_dat.len() {
-            c_dat.push(a_dat[i] + scal);
+        for a in &a_dat {
+            c_dat.push(a + scal);
         }
         let mut a = Matrix::from_slice_cm(&a_dat, rows, cols);
         a.add_scalar(scal);
@@ -195,6 +250,97 @@ fn transpose() {
     println!("\x1b[0;32mpassed\x1b[0m");
 }
 
+fn row_major() {
+    {
+        let rm = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let a = Matrix::from_slice_rm(&rm, 2, 3);
+        assert_eq!(a.to_vec(), vec![1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+        assert_eq!(a.to_vec_rm(), rm);
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn npy_round_trip() {
+    {
+        let path = std::env::temp_dir().join("neural_round_trip.npy");
+        let a = Matrix::from_slice_cm(&[1.5, 1.0, 2.5, 2.5, 2.0, 3.5], 3, 2);
+        a.write_npy(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!((bytes.len() - 6 * 4) % 64, 0);
+        let b = Matrix::read_npy(&path).unwrap();
+        assert_eq!(b.size(), (3, 2));
+        assert_eq!(a.to_vec(), b.to_vec());
+        std::fs::remove_file(&path).unwrap();
+    }
+    {
+        // numpy.save of np.array([[1, 2], [3, 4]], dtype='<f8')
+        let mut bytes = b"\x93NUMPY\x01\x00\x76\x00".to_vec();
+        let header = "{'descr': '<f8', 'fortran_order': False, 'shape': (2, 2), }";
+        bytes.extend_from_slice(format!("{header:<117}\n").as_bytes());
+        for v in [1.0f64, 2.0, 3.0, 4.0] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        let m = npy::read(&mut &bytes[..]).unwrap();
+        assert_eq!(m.to_vec(), vec![1.0, 3.0, 2.0, 4.0]);
+        let truncated = npy::read(&mut &bytes[..bytes.len() - 1]);
+        assert!(matches!(truncated, Err(npy::Error::Truncated)));
+    }
+    {
+        let a = Matrix::from_slice_cm(&[0.5, -1.25, 3.0], 3, 1);
+        let mut bytes = Vec::new();
+        npy::write_as(&a, &mut bytes, npy::Dtype::F64).unwrap();
+        assert_eq!((bytes.len() - 3 * 8) % 64, 0);
+        assert_eq!(npy::read(&mut &bytes[..]).unwrap().to_vec(), a.to_vec());
+    }
+    {
+        // Shapes announcing more data than the input holds or than fits in
+        // memory fail without allocating it
+        for shape in ["(1000000000, 1000000000)", "(18446744073709551615, 2)"] {
+            let mut bytes = b"\x93NUMPY\x01\x00\x76\x00".to_vec();
+            let header = format!("{{'descr': '<f4', 'fortran_order': True, 'shape': {shape}, }}");
+            bytes.extend_from_slice(format!("{header:<117}\n").as_bytes());
+            bytes.extend_from_slice(&[0; 16]);
+            let result = npy::read(&mut &bytes[..]);
+            assert!(matches!(
+                result,
+                Err(npy::Error::Truncated | npy::Error::InvalidHeader(_))
+            ));
+        }
+    }
+    {
+        // Empty arrays have no matrix to read into
+        for (shape, dims) in [("(0,)", vec![0]), ("(3, 0)", vec![3, 0])] {
+            let mut bytes = b"\x93NUMPY\x01\x00\x76\x00".to_vec();
+            let header = format!("{{'descr': '<f4', 'fortran_order': True, 'shape': {shape}, }}");
+            bytes.extend_from_slice(format!("{header:<117}\n").as_bytes());
+            match npy::read(&mut &bytes[..]) {
+                Err(npy::Error::UnsupportedShape(shape)) => assert_eq!(shape, dims),
+                other => panic!("unexpected result: {other:?}"),
+            }
+        }
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+#[cfg(feature = "npz")]
+fn npz_round_trip() {
+    {
+        let mut buf = std::io::Cursor::new(Vec::new());
+        let a = Matrix::from_slice_cm(&[1.0, 2.0, 3.0], 3, 1);
+        let b = Matrix::from_slice_cm(&[4.0, 5.0, 6.0, 7.0], 2, 2);
+        npy::write_npz(&[("a", &a), ("b", &b)], &mut buf).unwrap();
+        buf.set_position(0);
+        let read = npy::read_npz(buf).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].0, "a");
+        assert_eq!(read[0].1.to_vec(), a.to_vec());
+        assert_eq!(read[1].0, "b");
+        assert_eq!(read[1].1.size(), (2, 2));
+        assert_eq!(read[1].1.to_vec(), b.to_vec());
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
 #[derive(Debug, Clone)]
 struct CPUMatrix {
     pub data: Vec<Vec<f32>>,
@@ -212,7 +358,7 @@ impl CPUMatrix {
     }
 
     pub fn to_vec(&self) -> Vec<f32> {
-        self.data.iter().flatten().map(|v| *v).collect::<Vec<f32>>()
+        self.data.iter().flatten().copied().collect::<Vec<f32>>()
     }
 
     pub fn size(&self) -> (usize, usize) {
@@ -336,6 +482,1278 @@ fn sigmoid() {
     println!("\x1b[0;32mpassed\x1b[0m");
 }
 
+fn activations() {
+    {
+        let dat = vec![-2.0, -0.5, 0.0, 0.5, 2.0];
+        let mut relu = Matrix::from_slice(&dat);
+        relu.relu();
+        assert_eq!(relu.to_vec(), vec![0.0, 0.0, 0.0, 0.5, 2.0]);
+
+        let mut tanh = Matrix::from_slice(&dat);
+        tanh.tanh();
+        for (got, v) in tanh.to_vec().iter().zip(&dat) {
+            assert!((got - v.tanh()).abs() < 0.0001);
+        }
+
+        let mut softmax = Matrix::from_slice(&dat);
+        softmax.softmax();
+        let sum = dat.iter().map(|v| v.exp()).sum::<f32>();
+        for (got, v) in softmax.to_vec().iter().zip(&dat) {
+            assert!((got - v.exp() / sum).abs() < 0.0001);
+        }
+    }
+    {
+        // More values than threads in a block
+        let dat = (0..3000)
+            .map(|i| (i % 17) as f32 / 4.0)
+            .collect::<Vec<f32>>();
+        let mut softmax = Matrix::from_slice(&dat);
+        softmax.softmax();
+        let expected = labels::probabilities(&dat);
+        for (got, expected) in softmax.to_vec().iter().zip(&expected) {
+            assert!((got - expected).abs() < 1e-6);
+        }
+
+        let errors = (0..3000).map(|i| (i % 5) as f32).collect::<Vec<f32>>();
+        let mut derivative = Matrix::from_slice(&dat);
+        softmax.dsoftmax(&Matrix::from_slice(&errors), &mut derivative);
+        let dot = expected
+            .iter()
+            .zip(&errors)
+            .map(|(y, e)| y * e)
+            .sum::<f32>();
+        for ((got, y), e) in derivative.to_vec().iter().zip(&expected).zip(&errors) {
+            assert!((got - y * (e - dot)).abs() < 1e-6);
+        }
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn backward_apply_gradients() {
+    {
+        let inputs = [0.2, 0.9, 0.4];
+        let targets = [1.0, 0.0];
+        // Weights come from a fixed seed so both networks start out identical
+        let mut a = NeuralNetwork::new(3, vec![4], 2);
+        let mut b = NeuralNetwork::new(3, vec![4], 2);
+        a.set_learning_rate(0.5);
+        b.set_learning_rate(0.5);
+        a.train(&inputs, &targets);
+        let gradients = b.backward(&inputs, &targets);
+        b.apply_gradients(&gradients);
+        assert_eq!(
+            a.feedforward(inputs.to_vec()),
+            b.feedforward(inputs.to_vec())
+        );
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn accumulate_step() {
+    {
+        let samples = [([0.2, 0.9, 0.4], [1.0, 0.0]), ([0.7, 0.1, 0.3], [0.0, 1.0])];
+        let mut a = NeuralNetwork::new(3, vec![4], 2);
+        let mut b = NeuralNetwork::new(3, vec![4], 2);
+        a.set_learning_rate(0.5);
+        b.set_learning_rate(0.5);
+        for (inputs, targets) in &samples {
+            let gradients = a.backward(inputs, targets);
+            a.accumulate_gradients(&gradients);
+            b.accumulate(inputs, targets);
+        }
+        assert_eq!(a.accumulated(), 2);
+        a.step();
+        b.step();
+        assert_eq!(b.accumulated(), 0);
+        for (inputs, _) in &samples {
+            assert_eq!(
+                a.feedforward(inputs.to_vec()),
+                b.feedforward(inputs.to_vec())
+            );
+        }
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn save_load() {
+    {
+        let path = std::env::temp_dir().join("neural_save_load.nn");
+        let inputs = vec![0.2, 0.9, 0.4];
+        let mut nn = NeuralNetwork::new(3, vec![5, 4], 2);
+        nn.set_learning_rate(0.25);
+        nn.save(&path).unwrap();
+        let mut loaded = NeuralNetwork::load(&path).unwrap();
+        assert_eq!(loaded.learning_rate(), 0.25);
+        assert_eq!(
+            nn.feedforward(inputs.clone()),
+            loaded.feedforward(inputs.clone())
+        );
+
+        let bytes = std::fs::read(&path).unwrap();
+        let truncated = binary::read(&mut &bytes[..bytes.len() - 5]);
+        assert!(matches!(truncated, Err(binary::Error::Truncated)));
+        let mut corrupted = bytes.clone();
+        corrupted[bytes.len() - 10] ^= 0xFF;
+        let corrupted = binary::read(&mut &corrupted[..]);
+        assert!(matches!(
+            corrupted,
+            Err(binary::Error::ChecksumMismatch { .. })
+        ));
+        let bad_magic = binary::read(&mut &bytes[1..]);
+        assert!(matches!(bad_magic, Err(binary::Error::BadMagic)));
+        std::fs::remove_file(&path).unwrap();
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+#[cfg(feature = "serde")]
+fn serde_round_trip() {
+    {
+        let inputs = vec![0.2, 0.9, 0.4];
+        let mut nn = NeuralNetwork::new(3, vec![5, 4], 2);
+        let json = serde_json::to_string(&nn).unwrap();
+        let mut loaded: NeuralNetwork = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&loaded).unwrap());
+        assert_eq!(
+            nn.feedforward(inputs.clone()),
+            loaded.feedforward(inputs.clone())
+        );
+
+        let m = Matrix::from_slice_cm(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 3, 2);
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(
+            json,
+            r#"{"rows":3,"columns":2,"data":[1.0,2.0,3.0,4.0,5.0,6.0]}"#
+        );
+        let bad = r#"{"rows":3,"columns":3,"data":[1.0,2.0]}"#;
+        assert!(serde_json::from_str::<Matrix>(bad).is_err());
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+#[cfg(feature = "safetensors")]
+fn safetensors_round_trip() {
+    {
+        let path = std::env::temp_dir().join("neural_round_trip.safetensors");
+        let inputs = vec![0.2, 0.9, 0.4];
+        let mut nn = NeuralNetwork::new(3, vec![5], 2);
+        nn.save_safetensors(&path).unwrap();
+        let mut loaded = NeuralNetwork::load_safetensors(&path).unwrap();
+        assert_eq!(
+            nn.feedforward(inputs.clone()),
+            loaded.feedforward(inputs.clone())
+        );
+
+        let bytes = std::fs::read(&path).unwrap();
+        let tensors = safetensors::SafeTensors::deserialize(&bytes).unwrap();
+        let weight = tensors.tensor("layers.1.weight").unwrap();
+        assert_eq!(weight.shape(), &[5, 3]);
+        let first_row = weight.data()[..12]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<f32>>();
+        assert_eq!(first_row, nn.layers()[1].weights.to_vec_rm()[..3]);
+        std::fs::remove_file(&path).unwrap();
+    }
+    {
+        let zeros = [0; 64];
+        let view = |shape: Vec<usize>| {
+            let data = &zeros[..4 * shape.iter().product::<usize>()];
+            safetensors::tensor::TensorView::new(safetensors::Dtype::F32, shape, data).unwrap()
+        };
+        let bytes = safetensors::serialize(
+            [
+                ("layers.0.weight", view(vec![0, 3])),
+                ("layers.0.bias", view(vec![0])),
+            ],
+            &None,
+        )
+        .unwrap();
+        match neural::format::safetensors::from_bytes(&bytes) {
+            Err(neural::format::safetensors::Error::InvalidArchitecture(msg)) => {
+                assert_eq!(msg, "layer 0 has shape 0x3")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        let bytes = safetensors::serialize(
+            [
+                ("layers.0.weight", view(vec![3])),
+                ("layers.0.bias", view(vec![3])),
+            ],
+            &None,
+        )
+        .unwrap();
+        match neural::format::safetensors::from_bytes(&bytes) {
+            Err(neural::format::safetensors::Error::InvalidArchitecture(msg)) => {
+                assert_eq!(msg, "layer 0 weight has shape [3], expected two dimensions")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn onnx_export() {
+    {
+        let nn = NeuralNetwork::new(3, vec![5], 2);
+        let bytes = onnx::to_bytes(&nn);
+        // ir_version field comes first
+        assert_eq!(bytes[..2], [0x08, onnx::IR_VERSION as u8]);
+        let count = |needle: &[u8]| bytes.windows(needle.len()).filter(|w| *w == needle).count();
+        assert_eq!(count(b"Gemm"), 3);
+        assert_eq!(count(b"Sigmoid"), 3);
+        assert_eq!(count(b"layers.2.weight"), 2);
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn onnx_import() {
+    {
+        let inputs = vec![0.2, 0.9, 0.4];
+        let mut nn = NeuralNetwork::from_layers(vec![
+            layer(4, 3, Activation::Relu),
+            layer(4, 4, Activation::Tanh),
+            layer(3, 4, Activation::Identity),
+            layer(2, 3, Activation::Softmax),
+        ]);
+        let mut imported = onnx::from_bytes(&onnx::to_bytes(&nn)).unwrap();
+        assert_eq!(imported.layers().len(), 4);
+        assert_eq!(imported.layers()[2].activation, Activation::Identity);
+        assert_eq!(
+            nn.feedforward(inputs.clone()),
+            imported.feedforward(inputs.clone())
+        );
+    }
+    {
+        // ModelProto { graph: GraphProto { node: [NodeProto { op_type: "Conv" }] } }
+        let node = [&[0x22, 4][..], b"Conv"].concat();
+        let graph = [&[0x0A, node.len() as u8][..], &node].concat();
+        let model = [&[0x3A, graph.len() as u8][..], &graph].concat();
+        match onnx::from_bytes(&model) {
+            Err(onnx::Error::UnsupportedOperators(ops)) => assert_eq!(ops, vec!["Conv"]),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+    {
+        // A Gemm writing the tensor it reads loops back onto itself
+        let field = |tag: u8, value: &[u8]| [&[tag, value.len() as u8][..], value].concat();
+        let tensor = |name: &str, dims: &[u8]| {
+            let values = vec![0; 4 * dims.iter().product::<u8>() as usize];
+            let dims = dims.iter().flat_map(|d| [0x08, *d]).collect::<Vec<u8>>();
+            [
+                dims,
+                vec![0x10, 1],
+                field(0x22, &values),
+                field(0x42, name.as_bytes()),
+            ]
+            .concat()
+        };
+        let node = [
+            field(0x0A, b"x"),
+            field(0x0A, b"w"),
+            field(0x0A, b"b"),
+            field(0x12, b"x"),
+            field(0x1A, b"gemm"),
+            field(0x22, b"Gemm"),
+        ]
+        .concat();
+        let graph = [
+            field(0x0A, &node),
+            field(0x2A, &tensor("w", &[2, 2])),
+            field(0x2A, &tensor("b", &[2])),
+            field(0x5A, &field(0x0A, b"x")),
+            field(0x62, &field(0x0A, b"y")),
+        ]
+        .concat();
+        match onnx::from_bytes(&field(0x3A, &graph)) {
+            Err(onnx::Error::InvalidGraph(msg)) => {
+                assert_eq!(msg, "Gemm `gemm` is part of a cycle")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        let empty = [field(0x0A, &node), field(0x2A, &tensor("w", &[0, 2]))].concat();
+        match onnx::from_bytes(&field(0x3A, &empty)) {
+            Err(onnx::Error::UnsupportedTensor(msg)) => {
+                assert_eq!(msg, "`w` has dims [0, 2] with a zero dimension")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        // 2^32 * 2^32 elements overflow a usize
+        let huge = [
+            &[0x08, 0x80, 0x80, 0x80, 0x80, 0x10][..],
+            &[0x08, 0x80, 0x80, 0x80, 0x80, 0x10],
+            &[0x10, 1],
+            &field(0x42, b"w"),
+        ]
+        .concat();
+        match onnx::from_bytes(&field(0x3A, &field(0x2A, &huge))) {
+            Err(onnx::Error::UnsupportedTensor(msg)) => assert!(msg.starts_with("`w` has dims")),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn checkpoint_resume() {
+    {
+        let samples = [
+            ([0.2, 0.9, 0.4], [1.0, 0.0]),
+            ([0.7, 0.1, 0.3], [0.0, 1.0]),
+            ([0.5, 0.5, 0.9], [1.0, 1.0]),
+        ];
+        let train = |nn: &mut NeuralNetwork, state: &mut TrainingState, steps: u64| {
+            for _ in 0..steps {
+                let (inputs, targets) = samples[state.rng.gen_range(0..samples.len())];
+                nn.accumulate(&inputs, &targets);
+                state.step += 1;
+                if state.step % 2 == 0 {
+                    nn.step();
+                }
+            }
+        };
+
+        let dir = std::env::temp_dir().join("neural_checkpoints");
+        let _ = std::fs::remove_dir_all(&dir);
+        let checkpoints = CheckpointManager::new(&dir, 2).unwrap();
+        let mut nn = NeuralNetwork::new(3, vec![4], 2);
+        let mut state = TrainingState::new(42);
+        for _ in 0..3 {
+            train(&mut nn, &mut state, 3);
+            checkpoints.save(&nn, &state).unwrap();
+        }
+        assert_eq!(checkpoints.checkpoints().unwrap().len(), 2);
+
+        let (mut resumed, mut resumed_state) = checkpoints.resume().unwrap().unwrap();
+        assert_eq!(resumed_state.step, 9);
+        assert_eq!(resumed.accumulated(), 1);
+        train(&mut nn, &mut state, 5);
+        train(&mut resumed, &mut resumed_state, 5);
+        assert_eq!(
+            nn.feedforward(samples[0].0.to_vec()),
+            resumed.feedforward(samples[0].0.to_vec())
+        );
+        assert_eq!(state.rng.gen::<u64>(), resumed_state.rng.gen::<u64>());
+
+        let bytes = checkpoint::to_bytes(&nn, &state).unwrap();
+        let truncated = checkpoint::from_bytes(&bytes[..bytes.len() - 1]);
+        assert!(matches!(truncated, Err(checkpoint::Error::Truncated)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn data_loader() {
+    {
+        let samples = (0..10)
+            .map(|i| (vec![i as f32, -(i as f32)], vec![i as f32]))
+            .collect::<Vec<_>>();
+        let dataset = InMemoryDataset::from_samples(&samples);
+        assert_eq!(dataset.len(), 10);
+        assert_eq!(dataset.input(3), &[3.0, -3.0]);
+
+        let mut loader = DataLoader::new(&dataset, 3);
+        assert_eq!(loader.len(), 4);
+        let batches = loader.next_epoch().collect::<Vec<_>>();
+        assert_eq!(batches[1].indices, vec![3, 4, 5]);
+        assert_eq!(batches[1].inputs.size(), (2, 3));
+        assert_eq!(
+            batches[1].inputs.to_vec(),
+            vec![3.0, -3.0, 4.0, -4.0, 5.0, -5.0]
+        );
+        assert_eq!(batches[3].len(), 1);
+
+        let mut shuffled = DataLoader::new(&dataset, 3).shuffle(7).drop_last(true);
+        assert_eq!(shuffled.len(), 3);
+        let first = shuffled
+            .next_epoch()
+            .flat_map(|b| b.indices)
+            .collect::<Vec<_>>();
+        let second = shuffled
+            .next_epoch()
+            .flat_map(|b| b.indices)
+            .collect::<Vec<_>>();
+        assert_eq!(first.len(), 9);
+        assert_ne!(first, second);
+        let mut resumed = DataLoader::new(&dataset, 3).shuffle(7).drop_last(true);
+        resumed.set_epoch(1);
+        let again = resumed
+            .next_epoch()
+            .flat_map(|b| b.indices)
+            .collect::<Vec<_>>();
+        assert_eq!(second, again);
+        let mut all = DataLoader::new(&dataset, 4).shuffle(7).order(0);
+        all.sort();
+        assert_eq!(all, (0..10).collect::<Vec<_>>());
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn idx_round_trip() {
+    {
+        let arrays = [
+            IdxArray::new(vec![2, 3], Data::U8(vec![0, 1, 2, 253, 254, 255])),
+            IdxArray::new(vec![3], Data::I8(vec![-128, 0, 127])),
+            IdxArray::new(vec![1, 2, 2], Data::I16(vec![-300, 1, 2, 300])),
+            IdxArray::new(vec![2], Data::I32(vec![-70000, 70000])),
+            IdxArray::new(vec![2, 1], Data::F32(vec![1.5, -0.25])),
+            IdxArray::new(vec![1], Data::F64(vec![std::f64::consts::PI])),
+        ];
+        for array in &arrays {
+            let bytes = idx::to_bytes(array);
+            assert_eq!(&idx::from_bytes(&bytes).unwrap(), array);
+            let truncated = idx::from_bytes(&bytes[..bytes.len() - 1]);
+            assert!(matches!(truncated, Err(idx::Error::Truncated)));
+        }
+        // Header of an MNIST label file
+        let labels = [0x00, 0x00, 0x08, 0x01, 0x00, 0x00, 0x00, 0x02, 7, 3];
+        let labels = idx::from_bytes(&labels).unwrap();
+        assert_eq!(labels.dims, vec![2]);
+        assert_eq!(labels.data.to_f32(), vec![7.0, 3.0]);
+        let bad_magic = idx::from_bytes(&[0x01, 0x00, 0x08, 0x01, 0, 0, 0, 0]);
+        assert!(matches!(bad_magic, Err(idx::Error::BadMagic(_))));
+        let bad_type = idx::from_bytes(&[0x00, 0x00, 0x07, 0x01, 0, 0, 0, 0]);
+        assert!(matches!(bad_type, Err(idx::Error::UnknownType(0x07))));
+    }
+    #[cfg(feature = "gzip")]
+    {
+        let path = std::env::temp_dir().join("neural_round_trip.idx.gz");
+        let array = IdxArray::new(vec![2, 2], Data::U8(vec![1, 2, 3, 4]));
+        idx::write_file(&path, &array).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap()[..2], [0x1F, 0x8B]);
+        assert_eq!(idx::read_file(&path).unwrap(), array);
+        std::fs::remove_file(&path).unwrap();
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn mnist_loader() {
+    let dir = std::env::temp_dir().join("neural_mnist_loader");
+    std::fs::create_dir_all(&dir).unwrap();
+    // Two 2x3 images
+    let images = IdxArray::new(
+        vec![2, 2, 3],
+        Data::U8(vec![0, 51, 102, 153, 204, 255, 255, 0, 0, 0, 0, 0]),
+    );
+    idx::write_file(dir.join("t10k-images-idx3-ubyte"), &images).unwrap();
+    idx::write_file(
+        dir.join("t10k-labels-idx1-ubyte"),
+        &IdxArray::new(vec![2], Data::U8(vec![7, 0])),
+    )
+    .unwrap();
+    let tests = MnistLoader::new(&dir).load(Split::Test).unwrap();
+    assert_eq!(tests.len(), 2);
+    assert_eq!(tests.image_size(), (2, 3));
+    assert_eq!(tests.labels(), &[7, 0]);
+    assert_eq!(tests.input(0), &[0.0, 0.2, 0.4, 0.6, 0.8, 1.0]);
+    assert_eq!(tests.target_len(), 10);
+    assert_eq!(tests.target(0)[7], 1.0);
+    assert_eq!(tests.target(0).iter().sum::<f32>(), 1.0);
+    let raw = MnistLoader::new(&dir)
+        .normalization(mnist::Normalization::None)
+        .load(Split::Test)
+        .unwrap();
+    assert_eq!(raw.input(1), &[255.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    assert!(matches!(
+        MnistLoader::new(&dir).load(Split::Train),
+        Err(mnist::Error::MissingFile(_))
+    ));
+
+    // EMNIST letters are stored transposed and labelled from 1
+    let letters = Variant::Emnist(EmnistSplit::Letters);
+    idx::write_file(dir.join("emnist-letters-test-images-idx3-ubyte"), &images).unwrap();
+    idx::write_file(
+        dir.join("emnist-letters-test-labels-idx1-ubyte"),
+        &IdxArray::new(vec![2], Data::U8(vec![1, 26])),
+    )
+    .unwrap();
+    let tests = MnistLoader::new(&dir)
+        .variant(letters)
+        .normalization(mnist::Normalization::None)
+        .load(Split::Test)
+        .unwrap();
+    assert_eq!(tests.labels(), &[0, 25]);
+    assert_eq!(tests.target_len(), 26);
+    assert_eq!(tests.input(0), &[0.0, 102.0, 204.0, 51.0, 153.0, 255.0]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn cifar_loader() {
+    let dir = std::env::temp_dir().join("neural_cifar_loader");
+    std::fs::create_dir_all(&dir).unwrap();
+    // Two CIFAR-100 records: coarse label, fine label, then the red, green
+    // and blue planes
+    let mut bytes = Vec::new();
+    for (coarse, fine, color) in [(3u8, 42u8, [255u8, 0, 51]), (19, 99, [0, 255, 0])] {
+        bytes.extend_from_slice(&[coarse, fine]);
+        for channel in color {
+            bytes.extend(std::iter::repeat_n(channel, cifar::ROWS * cifar::COLUMNS));
+        }
+    }
+    std::fs::write(dir.join("test.bin"), &bytes).unwrap();
+
+    let loader = CifarLoader::new(&dir).variant(cifar::Variant::Cifar100);
+    let tests = loader.load(cifar::Split::Test).unwrap();
+    assert_eq!(tests.len(), 2);
+    assert_eq!(tests.input_len(), cifar::IMAGE_LEN);
+    assert_eq!(tests.labels(), &[42, 99]);
+    assert_eq!(tests.coarse_labels(), Some(&[3, 19][..]));
+    assert_eq!(tests.target_len(), 100);
+    assert_eq!(tests.target(0)[42], 1.0);
+    let plane = cifar::ROWS * cifar::COLUMNS;
+    assert_eq!(tests.input(0)[0], 1.0);
+    assert_eq!(tests.input(0)[plane], 0.0);
+    assert_eq!(tests.input(0)[2 * plane], 0.2);
+    let (mean, std) = tests.channel_stats().unwrap();
+    for (stat, expected) in mean.iter().chain(&std).zip([0.5, 0.5, 0.1, 0.5, 0.5, 0.1]) {
+        assert!((stat - expected).abs() < 1e-6);
+    }
+
+    let coarse = loader
+        .clone()
+        .labels(cifar::LabelKind::Coarse)
+        .normalization(cifar::Normalization::Standardize { mean, std })
+        .load(cifar::Split::Test)
+        .unwrap();
+    assert_eq!(coarse.labels(), &[3, 19]);
+    assert_eq!(coarse.target_len(), 20);
+    assert_eq!(coarse.target(1)[19], 1.0);
+    assert!((coarse.input(0)[0] - 1.0).abs() < 1e-5);
+    assert!((coarse.input(1)[2 * plane] + 1.0).abs() < 1e-5);
+
+    std::fs::write(dir.join("test.bin"), []).unwrap();
+    assert_eq!(
+        loader.load(cifar::Split::Test).unwrap().channel_stats(),
+        None
+    );
+
+    std::fs::write(dir.join("test.bin"), &bytes[..bytes.len() - 1]).unwrap();
+    assert!(matches!(
+        loader.load(cifar::Split::Test),
+        Err(cifar::Error::Truncated(_))
+    ));
+    assert!(matches!(
+        CifarLoader::new(&dir).load(cifar::Split::Test),
+        Err(cifar::Error::MissingFile(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn csv_loader() {
+    let text = "age,city,income,label\n\
+                31,Oslo,52.5,1\n\
+                \"45\",\"Bergen, west\",,0\n\
+                \n\
+                28,Oslo,61.5,1\n";
+    let dataset = CsvLoader::new()
+        .categorical(["city"])
+        .missing(MissingValues::Mean)
+        .parse(text)
+        .unwrap();
+    assert_eq!(dataset.len(), 3);
+    assert_eq!(dataset.features()[1].name, "city");
+    assert_eq!(
+        dataset.features()[1].categories,
+        Some(vec!["Bergen, west".to_string(), "Oslo".to_string()])
+    );
+    assert_eq!(dataset.input_len(), 4);
+    assert_eq!(dataset.input(0), &[31.0, 0.0, 1.0, 52.5]);
+    assert_eq!(dataset.input(1), &[45.0, 1.0, 0.0, 57.0]);
+    assert_eq!(dataset.target(1), &[0.0]);
+
+    let dataset = CsvLoader::new()
+        .features([2, 0])
+        .targets(["city"])
+        .categories("city", &["Oslo", "Bergen, west", "Trondheim"])
+        .missing(MissingValues::Skip)
+        .parse(text)
+        .unwrap();
+    assert_eq!(dataset.len(), 2);
+    assert_eq!(dataset.input(1), &[61.5, 28.0]);
+    assert_eq!(dataset.target(0), &[1.0, 0.0, 0.0]);
+
+    // No header, semicolon separated
+    let dataset = CsvLoader::new()
+        .delimiter(';')
+        .parse("1;2;0.5\n3;4;1.5\n")
+        .unwrap();
+    assert_eq!(dataset.len(), 2);
+    assert_eq!(dataset.input(1), &[3.0, 4.0]);
+    assert_eq!(dataset.target(1), &[1.5]);
+
+    let missing = CsvLoader::new().categorical(["city"]).parse(text);
+    assert!(matches!(
+        missing,
+        Err(csv::Error::MissingValue { line: 3, .. })
+    ));
+    let parse = CsvLoader::new().parse("a,b\n1,2\n3,x\n");
+    assert!(matches!(
+        parse,
+        Err(csv::Error::Parse { line: 3, ref column, ref value }) if column == "b" && value == "x"
+    ));
+    let ragged = CsvLoader::new().parse("a,b\n1,2\n3\n");
+    assert!(matches!(
+        ragged,
+        Err(csv::Error::FieldCount {
+            line: 3,
+            expected: 2,
+            found: 1
+        })
+    ));
+    let unknown = CsvLoader::new().targets(["missing"]).parse(text);
+    assert!(matches!(unknown, Err(csv::Error::UnknownColumn(_))));
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn libsvm_loader() {
+    let text = "# comment line\n\
+                1 1:0.5 3:-2 # trailing comment\n\
+                \n\
+                -1 qid:7 2:1.5\n";
+    let records = libsvm::parse(text).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].line, 2);
+    assert_eq!(records[0].features, vec![(0, 0.5), (2, -2.0)]);
+    assert_eq!(records[1].qid, Some(7));
+
+    let dataset = LibsvmLoader::new().parse(text).unwrap();
+    assert_eq!(dataset.input_len(), 3);
+    assert_eq!(dataset.input(0), &[0.5, 0.0, -2.0]);
+    assert_eq!(dataset.input(1), &[0.0, 1.5, 0.0]);
+    assert_eq!(dataset.target(1), &[-1.0]);
+    assert_eq!(dataset.qids(), &[None, Some(7)]);
+    let (inputs, targets) = LibsvmLoader::new()
+        .features(4)
+        .parse(text)
+        .unwrap()
+        .to_matrices();
+    assert_eq!(
+        inputs.to_vec(),
+        vec![0.5, 0.0, -2.0, 0.0, 0.0, 1.5, 0.0, 0.0]
+    );
+    assert_eq!(targets.to_vec(), vec![1.0, -1.0]);
+
+    let multi = LibsvmLoader::new()
+        .targets(Targets::MultiHot { classes: 4 })
+        .parse("0,3 1:1\n2 2:1\n")
+        .unwrap();
+    assert_eq!(multi.target(0), &[1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(multi.target(1), &[0.0, 0.0, 1.0, 0.0]);
+
+    let malformed = |text| libsvm::parse(text).unwrap_err();
+    assert!(matches!(
+        malformed("1 1:2\nx 1:2\n"),
+        libsvm::Error::Malformed { line: 2, .. }
+    ));
+    assert!(matches!(
+        malformed("1 0:2\n"),
+        libsvm::Error::Malformed { line: 1, .. }
+    ));
+    assert!(matches!(
+        malformed("1 2:1 1:1\n"),
+        libsvm::Error::Malformed { line: 1, .. }
+    ));
+    assert!(matches!(
+        malformed("1 1=1\n"),
+        libsvm::Error::Malformed { line: 1, .. }
+    ));
+    assert!(matches!(
+        LibsvmLoader::new().features(2).parse(text),
+        Err(libsvm::Error::IndexOutOfRange {
+            line: 2,
+            index: 3,
+            features: 2
+        })
+    ));
+    assert!(matches!(
+        LibsvmLoader::new()
+            .targets(Targets::MultiHot { classes: 2 })
+            .parse(text),
+        Err(libsvm::Error::InvalidClass { line: 4, .. })
+    ));
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn scalers() {
+    // Two features, four samples
+    let data = Matrix::from_slice_cm(&[1.0, 10.0, 2.0, 20.0, 3.0, 30.0, 4.0, 100.0], 2, 4);
+    let close = |a: &[f32], b: &[f32]| {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 1e-4, "{a} != {b}");
+        }
+    };
+
+    let mut standard = StandardScaler::new();
+    let scaled = standard.fit_transform(&data);
+    close(&standard.mean, &[2.5, 40.0]);
+    let values = scaled.to_vec();
+    let mean = values.iter().step_by(2).sum::<f32>() / 4.0;
+    let var = values.iter().step_by(2).map(|v| v * v).sum::<f32>() / 4.0;
+    close(&[mean, var], &[0.0, 1.0]);
+    close(
+        &standard.inverse_transform(&scaled).to_vec(),
+        &data.to_vec(),
+    );
+
+    let mut min_max = MinMaxScaler::new().range(-1.0, 1.0);
+    let scaled = min_max.fit_transform(&data);
+    close(
+        &scaled.to_vec()[..4],
+        &[-1.0, -1.0, -1.0 / 3.0, 10.0 / 90.0 * 2.0 - 1.0],
+    );
+    close(&min_max.inverse_transform(&scaled).to_vec(), &data.to_vec());
+
+    let mut robust = RobustScaler::new();
+    let scaled = robust.fit_transform(&data);
+    close(&robust.median, &[2.5, 25.0]);
+    close(&robust.iqr, &[1.5, 30.0]);
+    close(&robust.inverse_transform(&scaled).to_vec(), &data.to_vec());
+
+    let mut sample = [3.0, -4.0];
+    Normalizer::new(Norm::L2).transform_sample(&mut sample);
+    close(&sample, &[0.6, -0.8]);
+    let mut sample = [3.0, -4.0];
+    Normalizer::new(Norm::L1).transform_sample(&mut sample);
+    close(&sample, &[3.0 / 7.0, -4.0 / 7.0]);
+
+    let mut pipeline = Pipeline::new(NeuralNetwork::new(2, vec![3], 1))
+        .input_scaler(standard)
+        .output_scaler(min_max.clone());
+    let bytes = pipeline.to_bytes().unwrap();
+    let mut loaded = Pipeline::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.input_scaler, pipeline.input_scaler);
+    assert_eq!(loaded.output_scaler, Some(Scaler::MinMax(min_max)));
+    assert_eq!(loaded.predict(&[2.0, 30.0]), pipeline.predict(&[2.0, 30.0]));
+    let mut corrupted = bytes.clone();
+    corrupted[20] ^= 0xFF;
+    assert!(matches!(
+        Pipeline::from_bytes(&corrupted),
+        Err(preprocessing::Error::ChecksumMismatch { .. })
+    ));
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&pipeline).unwrap();
+        let mut loaded: Pipeline = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.input_scaler, pipeline.input_scaler);
+        assert_eq!(loaded.predict(&[2.0, 30.0]), pipeline.predict(&[2.0, 30.0]));
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn label_encoding() {
+    let encoder = LabelEncoder::fit(["dog", "cat", "bird", "cat"]);
+    assert_eq!(encoder.classes(), &["bird", "cat", "dog"]);
+    assert_eq!(encoder.encode(&"dog"), Some(2));
+    assert_eq!(encoder.encode(&"fish"), None);
+    assert_eq!(encoder.decode(1), Some(&"cat"));
+    let targets = encoder.one_hot(&["cat", "bird"]).unwrap();
+    assert_eq!(targets.size(), (3, 2));
+    assert_eq!(targets.to_vec(), vec![0.0, 1.0, 0.0, 1.0, 0.0, 0.0]);
+    assert!(encoder.one_hot(&["fish"]).is_none());
+
+    assert_eq!(labels::one_hot_vec(2, 4), vec![0.0, 0.0, 1.0, 0.0]);
+    let multi = labels::multi_hot(&[vec![0, 3], vec![]], 4);
+    assert_eq!(multi.to_vec(), vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+
+    let output = [0.1, 0.7, 0.05, 0.7, 0.15];
+    assert_eq!(labels::argmax(&output), 1);
+    assert_eq!(
+        labels::top_k(&output, 3),
+        vec![(1, 0.7), (3, 0.7), (4, 0.15)]
+    );
+    let probabilities = labels::probabilities(&[1.0, 2.0, 3.0]);
+    assert!((probabilities.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    assert!((probabilities[2] - 0.66524096).abs() < 1e-6);
+    let outputs = Matrix::from_slice_cm(&[0.2, 0.8, 0.9, 0.1, 0.5, 0.5], 2, 3);
+    assert_eq!(labels::argmax_columns(&outputs), vec![1, 0, 0]);
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn classification_metrics() {
+    let actual = [0, 0, 0, 1, 1, 2, 2, 2, 2, 2];
+    let predicted = [0, 0, 1, 1, 2, 2, 2, 2, 0, 2];
+    assert_eq!(metrics::accuracy(&predicted, &actual), 0.7);
+
+    let outputs = [
+        vec![0.6, 0.3, 0.1],
+        vec![0.2, 0.5, 0.3],
+        vec![0.1, 0.5, 0.4],
+        vec![0.2, 0.2, 0.6],
+    ];
+    assert_eq!(metrics::top_k_accuracy(&outputs, &[0, 0, 2, 1], 1), 0.25);
+    assert_eq!(metrics::top_k_accuracy(&outputs, &[0, 0, 2, 1], 2), 0.5);
+    assert_eq!(metrics::top_k_accuracy(&outputs, &[0, 0, 2, 1], 3), 1.0);
+
+    let confusion = ConfusionMatrix::from_predictions(&actual, &predicted, 3);
+    assert_eq!(confusion.count(0, 1), 1);
+    assert_eq!(confusion.count(2, 0), 1);
+    assert_eq!(confusion.total(), 10);
+    assert_eq!(confusion.support(2), 5);
+    assert_eq!(confusion.accuracy(), 0.7);
+    assert!((confusion.precision(0) - 2.0 / 3.0).abs() < 1e-6);
+    assert_eq!(confusion.recall(1), 0.5);
+    assert!((confusion.f1(2) - 0.8).abs() < 1e-6);
+    let macro_f1 = (2.0 / 3.0 + 0.5 + 0.8) / 3.0;
+    assert!((confusion.f1_average(Average::Macro) - macro_f1).abs() < 1e-6);
+    assert!((confusion.f1_average(Average::Weighted) - 0.7).abs() < 1e-6);
+    assert_eq!(confusion.precision_average(Average::Micro), 0.7);
+    assert!((confusion.cohen_kappa() - 0.32 / 0.62).abs() < 1e-6);
+    assert_eq!(confusion.to_string(), "  0 1 2\n0 2 1 0\n1 0 1 1\n2 1 0 4");
+
+    // A class that is never predicted scores zero instead of NaN
+    let confusion = ConfusionMatrix::from_predictions(&[0, 1], &[0, 0], 2);
+    assert_eq!(confusion.precision(1), 0.0);
+    assert_eq!(confusion.f1(1), 0.0);
+
+    let report = ConfusionMatrix::from_predictions(&actual, &predicted, 3)
+        .report()
+        .with_names(&["cat", "dog", "bird"]);
+    assert_eq!(report.classes[2].support, 5);
+    let printed = report.to_string();
+    assert!(printed.contains("         dog    0.5000    0.5000    0.5000         2"));
+    assert!(printed.contains("    accuracy                        0.7000        10"));
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn threshold_metrics() {
+    let scores = [0.1, 0.4, 0.35, 0.8];
+    let labels = [false, false, true, true];
+    let roc = RocCurve::new(&scores, &labels);
+    assert_eq!(roc.thresholds, vec![f32::INFINITY, 0.8, 0.4, 0.35, 0.1]);
+    assert_eq!(roc.false_positive_rate, vec![0.0, 0.0, 0.5, 0.5, 1.0]);
+    assert_eq!(roc.true_positive_rate, vec![0.0, 0.5, 0.5, 1.0, 1.0]);
+    assert_eq!(roc.auc(), 0.75);
+    assert_eq!(metrics::roc_auc(&scores, &labels), 0.75);
+    let youden = roc.youden_threshold();
+    assert_eq!((youden.threshold, youden.score), (0.8, 0.5));
+
+    let pr = PrecisionRecallCurve::new(&scores, &labels);
+    assert_eq!(pr.recall, vec![0.5, 0.5, 1.0, 1.0]);
+    assert_eq!(pr.precision[1], 0.5);
+    assert!((metrics::average_precision(&scores, &labels) - 5.0 / 6.0).abs() < 1e-6);
+    let best = pr.max_f1_threshold();
+    assert_eq!(best.threshold, 0.35);
+    assert!((best.score - 0.8).abs() < 1e-6);
+
+    // Tied scores form a single point
+    assert_eq!(metrics::roc_auc(&[0.5, 0.5], &[true, false]), 0.5);
+    assert_eq!(metrics::roc_auc(&[0.9, 0.2], &[true, false]), 1.0);
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn regression_metrics() {
+    let targets = [[1.0, 10.0], [2.0, 20.0], [3.0, 30.0], [4.0, 40.0]];
+    let outputs = [
+        vec![1.5, 10.0],
+        vec![2.5, 20.0],
+        vec![3.5, 30.0],
+        vec![4.5, 40.0],
+    ];
+    let report = RegressionReport::new(&outputs, &targets);
+    let first = report.outputs[0];
+    assert_eq!((first.mse, first.rmse, first.mae), (0.25, 0.5, 0.5));
+    assert!((first.mape - 0.5 * (1.0 + 1.0 / 2.0 + 1.0 / 3.0 + 1.0 / 4.0) / 4.0).abs() < 1e-6);
+    assert!((first.r2 - 0.8).abs() < 1e-6);
+    // The outputs are off by a constant
+    assert_eq!(first.explained_variance, 1.0);
+    assert_eq!(report.outputs[1].mse, 0.0);
+    assert_eq!(report.outputs[1].r2, 1.0);
+
+    assert_eq!(report.average.mse, 0.125);
+    assert!((report.average.r2 - 0.9).abs() < 1e-6);
+    assert_eq!(metrics::mean_squared_error(&outputs, &targets), 0.125);
+    assert_eq!(metrics::root_mean_squared_error(&outputs, &targets), 0.25);
+    assert_eq!(metrics::mean_absolute_error(&outputs, &targets), 0.25);
+    assert!((metrics::r2_score(&outputs, &targets) - 0.9).abs() < 1e-6);
+    assert_eq!(metrics::explained_variance(&outputs, &targets), 1.0);
+    assert!(report.to_string().contains("average"));
+
+    // Constant targets
+    assert_eq!(metrics::r2_score(&[[2.0], [2.0]], &[[2.0], [2.0]]), 1.0);
+    assert_eq!(metrics::r2_score(&[[1.0], [3.0]], &[[2.0], [2.0]]), 0.0);
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn evaluation() {
+    {
+        let outputs = [[0.9], [0.4], [0.35], [0.1]];
+        let targets = [[1.0], [0.0], [1.0], [0.0]];
+        let report = Report::new(
+            &outputs,
+            &targets,
+            &[Metric::Accuracy, Metric::RocAuc, Metric::Classification],
+        );
+        assert_eq!(report.samples, 4);
+        assert!((report.loss - (0.01 + 0.16 + 0.4225 + 0.01) / 4.0).abs() < 1e-6);
+        assert_eq!(report.accuracy, Some(0.75));
+        assert_eq!(report.roc_auc, Some(0.75));
+        assert_eq!(
+            report.classification.as_ref().unwrap().classes[1].support,
+            2
+        );
+        assert_eq!(report.average_precision, None);
+        assert!(report.regression.is_none());
+        assert!(report.to_string().contains("ROC AUC: 0.7500"));
+    }
+    {
+        // A single output p counts as the classes 1 - p and p
+        let outputs = [[0.9], [0.4], [0.6]];
+        let targets = [[1.0], [1.0], [0.0]];
+        let metrics = [
+            Metric::TopKAccuracy(1),
+            Metric::TopKAccuracy(2),
+            Metric::RocAuc,
+        ];
+        let report = Report::new(&outputs, &targets, &metrics);
+        assert_eq!(report.top_k_accuracy[0], (1, 1.0 / 3.0));
+        assert_eq!(report.top_k_accuracy[1], (2, 1.0));
+
+        // All samples of one class
+        let report = Report::new(&outputs, &[[1.0], [1.0], [1.0]], &metrics);
+        assert!(report.roc_auc.unwrap().is_nan());
+
+        // The curves need a binary classifier
+        let report = Report::new(
+            &[[0.2, 0.8], [0.7, 0.3]],
+            &[[0.0, 1.0], [1.0, 0.0]],
+            &[Metric::RocAuc, Metric::AveragePrecision],
+        );
+        assert_eq!(report.roc_auc, None);
+        assert_eq!(report.average_precision, None);
+
+        let empty: [[f32; 1]; 0] = [];
+        let report = Report::new(&empty, &empty, &[Metric::Accuracy, Metric::Regression]);
+        assert_eq!(report.samples, 0);
+        assert!(report.loss.is_nan());
+        assert_eq!(report.accuracy, None);
+        assert!(report.regression.is_none());
+    }
+    {
+        let mut nn = NeuralNetwork::from_layers(vec![
+            layer(4, 2, Activation::Relu),
+            layer(3, 4, Activation::Softmax),
+        ]);
+        // More samples than fit in one evaluation batch
+        let dataset = synthetic::blobs(
+            300,
+            &[vec![0.0, 0.0], vec![2.0, 0.0], vec![0.0, 2.0]],
+            0.5,
+            3,
+        );
+
+        let batch = Batch::from_indices(&dataset, vec![0, 1, 2]);
+        let batched = nn.predict_batch(&batch.inputs).to_vec();
+        for (i, output) in batched.chunks_exact(3).enumerate() {
+            let single = nn.feedforward(dataset.input(i).to_vec());
+            for (a, b) in output.iter().zip(&single) {
+                assert!((a - b).abs() < 1e-5);
+            }
+        }
+
+        let metrics = [
+            Metric::Accuracy,
+            Metric::TopKAccuracy(2),
+            Metric::Classification,
+        ];
+        let report = nn.evaluate(&dataset, &metrics);
+        let outputs = (0..dataset.len())
+            .map(|i| nn.feedforward(dataset.input(i).to_vec()))
+            .collect::<Vec<_>>();
+        let targets = (0..dataset.len())
+            .map(|i| dataset.target(i))
+            .collect::<Vec<_>>();
+        let expected = Report::new(&outputs, &targets, &metrics);
+        assert_eq!(report.samples, 300);
+        assert!((report.loss - expected.loss).abs() < 1e-5);
+        assert_eq!(report.accuracy, expected.accuracy);
+        assert_eq!(report.top_k_accuracy[0].0, 2);
+        assert_eq!(report.classification, expected.classification);
+    }
+    {
+        // Layers as wide as MNIST's, so a batch of activations needs far
+        // more than one block of threads
+        let mut nn = NeuralNetwork::new(784, vec![16], 10);
+        let samples = (0..300)
+            .map(|i| {
+                let input = (0..784)
+                    .map(|j| ((i * 31 + j * 7) % 97) as f32 / 97.0)
+                    .collect::<Vec<f32>>();
+                (input, labels::one_hot_vec(i % 10, 10))
+            })
+            .collect::<Vec<_>>();
+        let dataset = InMemoryDataset::from_samples(&samples);
+
+        let report = nn.evaluate(&dataset, &[Metric::Accuracy]);
+        let outputs = (0..dataset.len())
+            .map(|i| nn.feedforward(dataset.input(i).to_vec()))
+            .collect::<Vec<_>>();
+        let targets = (0..dataset.len())
+            .map(|i| dataset.target(i))
+            .collect::<Vec<_>>();
+        let expected = Report::new(&outputs, &targets, &[Metric::Accuracy]);
+        assert_eq!(report.samples, 300);
+        assert!((report.loss - expected.loss).abs() < 1e-5);
+        assert_eq!(report.accuracy, expected.accuracy);
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn splits() {
+    // 30 samples of class 0, 10 of class 1
+    let samples = (0..40)
+        .map(|i| {
+            let target = if i < 30 {
+                vec![1.0, 0.0]
+            } else {
+                vec![0.0, 1.0]
+            };
+            (vec![i as f32], target)
+        })
+        .collect::<Vec<_>>();
+    let dataset = InMemoryDataset::from_samples(&samples);
+    let count_class_1 = |part: &Subset<'_, InMemoryDataset>| {
+        (0..part.len())
+            .filter(|i| part.target(*i)[1] == 1.0)
+            .count()
+    };
+
+    let parts = split::random_split(&dataset, &[0.5, 0.25, 0.25], 3);
+    assert_eq!(
+        parts.iter().map(|p| p.len()).collect::<Vec<_>>(),
+        vec![20, 10, 10]
+    );
+    let mut seen = parts
+        .iter()
+        .flat_map(|p| p.indices().to_vec())
+        .collect::<Vec<_>>();
+    seen.sort_unstable();
+    assert_eq!(seen, (0..40).collect::<Vec<_>>());
+    let again = split::random_split(&dataset, &[0.5, 0.25, 0.25], 3);
+    assert_eq!(parts[1].indices(), again[1].indices());
+    assert_eq!(parts[1].input(0), &[parts[1].indices()[0] as f32]);
+
+    let parts = split::stratified_split(&dataset, &[0.8, 0.2], 3);
+    assert_eq!((parts[0].len(), count_class_1(&parts[0])), (32, 8));
+    assert_eq!((parts[1].len(), count_class_1(&parts[1])), (8, 2));
+    let (training, test) = split::train_test_split(&dataset, 0.25, 9);
+    assert_eq!((training.len(), test.len()), (30, 10));
+
+    let folds = KFold::new(5)
+        .shuffle(1)
+        .stratified(true)
+        .fold_indices(&dataset);
+    for fold in &folds {
+        assert_eq!(fold.len(), 8);
+        assert_eq!(fold.iter().filter(|i| **i >= 30).count(), 2);
+    }
+    let folds = KFold::new(3).fold_indices(&dataset);
+    assert_eq!(folds[0], (0..14).collect::<Vec<_>>());
+    assert_eq!(folds[2].len(), 13);
+
+    let mut built = Vec::new();
+    let results = KFold::new(4).shuffle(2).stratified(true).cross_validate(
+        &dataset,
+        &[Metric::Accuracy, Metric::Regression],
+        |fold| {
+            built.push(fold);
+            NeuralNetwork::new(1, vec![3], 2)
+        },
+        |nn, training| {
+            assert_eq!(training.len(), 30);
+            nn.train(training.input(0), training.target(0));
+        },
+    );
+    assert_eq!(built, vec![0, 1, 2, 3]);
+    assert_eq!(results.folds.len(), 4);
+    for fold in &results.folds {
+        assert_eq!(fold.validation_len, 10);
+        assert!((0.0..=1.0).contains(&fold.report.accuracy.unwrap()));
+    }
+    let mean = results.folds.iter().map(|f| f.report.loss).sum::<f32>() / 4.0;
+    assert!((results.loss.mean - mean).abs() < 1e-6);
+    assert!(results.accuracy.is_some());
+    assert_eq!(results.roc_auc, None);
+    let mse = results.aggregate(|r| r.regression.as_ref().map(|r| r.average.mse));
+    assert!((mse.unwrap().mean - results.loss.mean).abs() < 1e-6);
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn synthetic_datasets() {
+    let xor = synthetic::xor(100, 0.0, 1);
+    assert_eq!((xor.len(), xor.input_len(), xor.target_len()), (100, 2, 2));
+    for i in 0..xor.len() {
+        let input = xor.input(i);
+        let class = usize::from(input[0] != input[1]);
+        assert_eq!(labels::argmax(xor.target(i)), class);
+    }
+    assert_eq!(synthetic::xor(100, 0.1, 1), synthetic::xor(100, 0.1, 1));
+    assert_ne!(synthetic::xor(100, 0.1, 1), synthetic::xor(100, 0.1, 2));
+    let empty = synthetic::moons(0, 0.1, 1);
+    assert_eq!(
+        (empty.len(), empty.input_len(), empty.target_len()),
+        (0, 2, 2)
+    );
+    assert!(synthetic::sine(0, 0.1, 1).is_empty());
+
+    let circles = synthetic::circles(50, 0.0, 0.5, 3);
+    for i in 0..circles.len() {
+        let input = circles.input(i);
+        let r = (input[0] * input[0] + input[1] * input[1]).sqrt();
+        let expected = if circles.target(i)[0] == 1.0 {
+            1.0
+        } else {
+            0.5
+        };
+        assert!((r - expected).abs() < 1e-5);
+    }
+
+    let spirals = synthetic::spirals(90, 3, 0.1, 4);
+    assert_eq!(spirals.target_len(), 3);
+    let moons = synthetic::moons(10, 0.05, 4);
+    assert_eq!(moons.target(1), &[0.0, 1.0]);
+
+    let blobs = synthetic::blobs(300, &[vec![5.0, 5.0, 5.0], vec![-5.0, -5.0, -5.0]], 0.5, 5);
+    assert_eq!(blobs.input_len(), 3);
+    for i in 0..blobs.len() {
+        let positive = blobs.input(i)[0] > 0.0;
+        assert_eq!(blobs.target(i)[0] == 1.0, positive);
+    }
+
+    let board = synthetic::checkerboard(200, 4, 6);
+    for i in 0..board.len() {
+        let input = board.input(i);
+        let field = (input[0] * 4.0) as usize + (input[1] * 4.0) as usize;
+        assert_eq!(board.target(i)[field % 2], 1.0);
+    }
+
+    let sine = synthetic::sine(50, 0.0, 7);
+    for i in 0..sine.len() {
+        assert!((sine.target(i)[0] - sine.input(i)[0].sin()).abs() < 1e-6);
+    }
+    let cubic = synthetic::polynomial(20, &[1.0, 0.0, -2.0, 0.5], (-2.0, 2.0), 0.0, 8);
+    for i in 0..cubic.len() {
+        let x = cubic.input(i)[0];
+        assert!((cubic.target(i)[0] - (1.0 - 2.0 * x * x + 0.5 * x * x * x)).abs() < 1e-5);
+    }
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn augmentation() {
+    let shape = ImageShape::new(2, 2, 3);
+    let image = (0..12).map(|v| v as f32).collect::<Vec<f32>>();
+    let augmented = |augmenter: &Augmenter, position| {
+        let mut copy = image.clone();
+        augmenter.apply_at(&mut copy, 0, position);
+        copy
+    };
+
+    let flip = Augmenter::new(shape, 1).then(HorizontalFlip { probability: 1.0 });
+    assert_eq!(
+        augmented(&flip, 0),
+        vec![2.0, 1.0, 0.0, 5.0, 4.0, 3.0, 8.0, 7.0, 6.0, 11.0, 10.0, 9.0]
+    );
+    let identity = Augmenter::new(shape, 1)
+        .then(Scale { min: 1.0, max: 1.0 })
+        .then(Translate {
+            max_dx: 0,
+            max_dy: 0,
+        })
+        .then(Rotate { max_degrees: 0.0 })
+        .then(GaussianNoise { std: 0.0 });
+    assert_eq!(augmented(&identity, 0), image);
+    let erase = Augmenter::new(shape, 1)
+        .background(-1.0)
+        .then(RandomErasing {
+            probability: 1.0,
+            min_area: 1.0,
+            max_area: 1.0,
+        });
+    let erased = augmented(&erase, 0);
+    assert!(erased.contains(&-1.0));
+    for i in 0..6 {
+        assert_eq!(erased[i] == -1.0, erased[i + 6] == -1.0);
+        assert!(erased[i] == -1.0 || erased[i] == image[i]);
+    }
+
+    // Shifts by whole pixels keep the values and fill in the background
+    let translate = Augmenter::new(shape, 2).then(Translate {
+        max_dx: 1,
+        max_dy: 1,
+    });
+    for position in 0..10 {
+        let shifted = augmented(&translate, position);
+        assert!(shifted
+            .iter()
+            .all(|v| v.fract() == 0.0 && (0.0..12.0).contains(v)));
+    }
+
+    let mnist = ImageShape::new(1, 8, 8);
+    let augmenter = Augmenter::new(mnist, 3)
+        .then(Rotate { max_degrees: 15.0 })
+        .then(Elastic {
+            alpha: 4.0,
+            sigma: 2.0,
+        })
+        .then(GaussianNoise { std: 0.1 });
+    let digit = (0..64).map(|i| (i % 7) as f32 / 7.0).collect::<Vec<f32>>();
+    let run = |epoch, position| {
+        let mut copy = digit.clone();
+        augmenter.apply_at(&mut copy, epoch, position);
+        copy
+    };
+    assert_eq!(run(0, 5), run(0, 5));
+    assert_ne!(run(0, 5), run(0, 6));
+    assert_ne!(run(0, 5), run(1, 5));
+
+    let dataset = InMemoryDataset::from_samples(&[
+        (digit.clone(), vec![1.0]),
+        (digit.clone(), vec![0.0]),
+        (digit.clone(), vec![1.0]),
+    ]);
+    let mut loader = DataLoader::new(&dataset, 2).augment(&augmenter);
+    loader.set_epoch(1);
+    let batches = loader.next_epoch().collect::<Vec<_>>();
+    let mut expected = run(1, 0);
+    expected.extend(run(1, 1));
+    assert_eq!(batches[0].inputs.to_vec(), expected);
+    assert_eq!(batches[1].inputs.to_vec(), run(1, 2));
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
+fn prefetching() {
+    let samples = (0..23)
+        .map(|i| (vec![i as f32; 4], vec![(i % 3) as f32]))
+        .collect::<Vec<_>>();
+    let dataset = InMemoryDataset::from_samples(&samples);
+    let augmenter = Augmenter::new(ImageShape::new(1, 2, 2), 5).then(GaussianNoise { std: 0.5 });
+    let mut loader = DataLoader::new(&dataset, 4).shuffle(11).augment(&augmenter);
+    let mut prefetching = loader.clone();
+
+    for _ in 0..2 {
+        let expected = loader
+            .next_epoch()
+            .map(|b| (b.indices, b.inputs.to_vec(), b.targets.to_vec()))
+            .collect::<Vec<_>>();
+        let prefetched = prefetching.prefetch_epoch(3, 2, |batches| {
+            assert_eq!(batches.len(), 6);
+            batches
+                .map(|b| (b.indices, b.inputs.to_vec(), b.targets.to_vec()))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(prefetched, expected);
+    }
+    assert_eq!(prefetching.epoch(), 2);
+
+    // Stopping early must not block on the workers
+    let first = prefetching.prefetch_epoch(4, 1, |mut batches| batches.next().unwrap().indices);
+    assert_eq!(first, loader.order(2)[..4]);
+    println!("\x1b[0;32mpassed\x1b[0m");
+}
+
 fn main() {
     init();
 
@@ -347,12 +1765,79 @@ fn main() {
     mat_sub_mat();
     print!("Testing add_matrix...");
     mat_add_mat();
+    print!("Testing add_scaled_matrix...");
+    mat_add_scaled_mat();
     print!("Testing add_scalar...");
     mat_add_scal();
     print!("Testing transpose_into...");
     transpose();
+    print!("Testing from_slice_rm/to_vec_rm...");
+    row_major();
+    print!("Testing npy...");
+    npy_round_trip();
+    #[cfg(feature = "npz")]
+    {
+        print!("Testing npz...");
+        npz_round_trip();
+    }
     print!("Testing product...");
     gemm();
     print!("Testing sigmoid...");
     sigmoid();
+    print!("Testing relu/tanh/softmax...");
+    activations();
+    print!("Testing backward/apply_gradients...");
+    backward_apply_gradients();
+    print!("Testing accumulate/step...");
+    accumulate_step();
+    print!("Testing save/load...");
+    save_load();
+    print!("Testing scalers...");
+    scalers();
+    print!("Testing label encoding...");
+    label_encoding();
+    print!("Testing classification metrics...");
+    classification_metrics();
+    print!("Testing ROC/PR curves...");
+    threshold_metrics();
+    print!("Testing regression metrics...");
+    regression_metrics();
+    print!("Testing evaluate...");
+    evaluation();
+    print!("Testing IDX...");
+    idx_round_trip();
+    print!("Testing MNIST loader...");
+    mnist_loader();
+    print!("Testing CIFAR loader...");
+    cifar_loader();
+    print!("Testing CSV loader...");
+    csv_loader();
+    print!("Testing LIBSVM loader...");
+    libsvm_loader();
+    print!("Testing splits/k-fold...");
+    splits();
+    print!("Testing synthetic datasets...");
+    synthetic_datasets();
+    print!("Testing prefetching...");
+    prefetching();
+    print!("Testing augmentation...");
+    augmentation();
+    print!("Testing DataLoader...");
+    data_loader();
+    print!("Testing checkpoints...");
+    checkpoint_resume();
+    print!("Testing ONNX export...");
+    onnx_export();
+    print!("Testing ONNX import...");
+    onnx_import();
+    #[cfg(feature = "serde")]
+    {
+        print!("Testing serde...");
+        serde_round_trip();
+    }
+    #[cfg(feature = "safetensors")]
+    {
+        print!("Testing safetensors...");
+        safetensors_round_trip();
+    }
 }
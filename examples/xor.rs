@@ -26,7 +26,7 @@ fn secs_to_human(secs: u64) -> String {
 fn main() {
     init();
 
-    let training_data = vec![
+    let training_data = [
         (vec![0.0, 1.0], vec![1.0]),
         (vec![1.0, 0.0], vec![1.0]),
         (vec![0.0, 0.0], vec![0.0]),
@@ -62,7 +62,7 @@ fn main() {
     std::io::stdout().flush().unwrap();
     for index in 0..TRAINING_ITERATIONS {
         let (inputs, target) = training_data.choose(&mut rng).unwrap();
-        nn.train(&inputs, &target);
+        nn.train(inputs, target);
         let elapsed_secs = start.elapsed().as_secs();
         if elapsed_secs - last > 0 {
             print!(
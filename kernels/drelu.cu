@@ -0,0 +1,7 @@
+extern "C" __global__ void
+drelu(float *A, float *B, unsigned int n) {
+    unsigned int i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i < n) {
+        B[i] = A[i] > 0.0 ? 1.0 : 0.0;
+    }
+}
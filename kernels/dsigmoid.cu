@@ -1,4 +1,7 @@
 extern "C" __global__ void
-dsigmoid(float *A, float *B) {
-    B[threadIdx.x] = A[threadIdx.x] * (1.0 - A[threadIdx.x]);
+dsigmoid(float *A, float *B, unsigned int n) {
+    unsigned int i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i < n) {
+        B[i] = A[i] * (1.0 - A[i]);
+    }
 }
@@ -0,0 +1,24 @@
+// Launched like softmax, reducing the dot product in shared memory
+extern "C" __global__ void
+dsoftmax(float *Y, float *E, float *B, unsigned int n) {
+    extern __shared__ float partial[];
+    unsigned int t = threadIdx.x;
+
+    float dot = 0.0;
+    for (unsigned int i = t; i < n; i += blockDim.x) {
+        dot += Y[i] * E[i];
+    }
+    partial[t] = dot;
+    __syncthreads();
+    for (unsigned int s = blockDim.x / 2; s > 0; s /= 2) {
+        if (t < s) {
+            partial[t] += partial[t + s];
+        }
+        __syncthreads();
+    }
+    dot = partial[0];
+
+    for (unsigned int i = t; i < n; i += blockDim.x) {
+        B[i] = Y[i] * (E[i] - dot);
+    }
+}
@@ -1,4 +1,7 @@
 extern "C" __global__ void
-mat_add_scalar(float *A, float s) {
-    A[threadIdx.x] += s;
-}
\ No newline at end of file
+mat_add_scalar(float *A, float s, unsigned int n) {
+    unsigned int i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i < n) {
+        A[i] += s;
+    }
+}
@@ -1,4 +1,7 @@
 extern "C" __global__ void
-mat_sub_mat(float *A, float *B, float *C) {
-    C[threadIdx.x] = A[threadIdx.x] - B[threadIdx.x];
+mat_sub_mat(float *A, float *B, float *C, unsigned int n) {
+    unsigned int i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i < n) {
+        C[i] = A[i] - B[i];
+    }
 }
@@ -0,0 +1,7 @@
+extern "C" __global__ void
+mat_tanh(float *A, unsigned int n) {
+    unsigned int i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i < n) {
+        A[i] = tanhf(A[i]);
+    }
+}
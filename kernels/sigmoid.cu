@@ -1,4 +1,7 @@
 extern "C" __global__ void
-sigmoid(float *A) {
-    A[threadIdx.x] = 1.0 / (1.0 + expf(-A[threadIdx.x]));
+sigmoid(float *A, unsigned int n) {
+    unsigned int i = blockIdx.x * blockDim.x + threadIdx.x;
+    if (i < n) {
+        A[i] = 1.0 / (1.0 + expf(-A[i]));
+    }
 }
@@ -0,0 +1,40 @@
+// One block of a power of two threads, each covering every blockDim.x-th
+// value, with partial maxima and sums reduced in shared memory
+extern "C" __global__ void
+softmax(float *A, unsigned int n) {
+    extern __shared__ float partial[];
+    unsigned int t = threadIdx.x;
+
+    float m = A[0];
+    for (unsigned int i = t; i < n; i += blockDim.x) {
+        m = fmaxf(m, A[i]);
+    }
+    partial[t] = m;
+    __syncthreads();
+    for (unsigned int s = blockDim.x / 2; s > 0; s /= 2) {
+        if (t < s) {
+            partial[t] = fmaxf(partial[t], partial[t + s]);
+        }
+        __syncthreads();
+    }
+    m = partial[0];
+    __syncthreads();
+
+    float sum = 0.0;
+    for (unsigned int i = t; i < n; i += blockDim.x) {
+        sum += expf(A[i] - m);
+    }
+    partial[t] = sum;
+    __syncthreads();
+    for (unsigned int s = blockDim.x / 2; s > 0; s /= 2) {
+        if (t < s) {
+            partial[t] += partial[t + s];
+        }
+        __syncthreads();
+    }
+    sum = partial[0];
+
+    for (unsigned int i = t; i < n; i += blockDim.x) {
+        A[i] = expf(A[i] - m) / sum;
+    }
+}
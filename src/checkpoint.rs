@@ -0,0 +1,290 @@
+//! Full training-state checkpoints.
+//!
+//! A checkpoint holds everything needed to continue a training run exactly
+//! where it stopped: the network in the [`crate::format::binary`] format,
+//! the gradient accumulators of [`NeuralNetwork::accumulate`], the step and
+//! epoch counters and the state of the run's random number generator.
+//! Learning-rate schedules computed from [`TrainingState::step`] therefore
+//! pick up at the same position.
+//!
+//! All integers and floats are little-endian.
+//!
+//! ```text
+//! magic          8 bytes  "NEURALCK"
+//! version        u32      currently 1
+//! step           u64
+//! epoch          u64
+//! rng_seed       32 bytes ChaCha8 seed
+//! rng_stream     u64
+//! rng_word_pos   u128
+//! network_len    u64
+//! network        network_len bytes, see crate::format::binary
+//! accumulated    u64      samples in the gradient accumulators
+//! for every layer:
+//!     weights_grad rows * columns f32, column major
+//!     bias_grad    rows f32
+//! checksum       u32      CRC-32 of every preceding byte
+//! ```
+
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    format::{binary, crc32, Cursor, Framing},
+    matrix::Matrix,
+    nn::NeuralNetwork,
+};
+
+pub const MAGIC: &[u8; 8] = b"NEURALCK";
+pub const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    /// The input ended before the announced amount of data was read.
+    Truncated,
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// Bytes follow the checksum.
+    TrailingData(usize),
+    Network(binary::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::BadMagic => write!(f, "not a checkpoint file (bad magic number)"),
+            Error::UnsupportedVersion(v) => {
+                write!(f, "unsupported checkpoint version {v} (expected {VERSION})")
+            }
+            Error::Truncated => write!(f, "checkpoint is truncated"),
+            Error::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch: expected {expected:#010x}, found {found:#010x}"
+            ),
+            Error::TrailingData(n) => write!(f, "{n} unexpected bytes after the checksum"),
+            Error::Network(e) => write!(f, "invalid network: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<Framing> for Error {
+    fn from(e: Framing) -> Self {
+        match e {
+            Framing::BadMagic => Error::BadMagic,
+            Framing::UnsupportedVersion(v) => Error::UnsupportedVersion(v),
+            Framing::Truncated => Error::Truncated,
+            Framing::ChecksumMismatch { expected, found } => {
+                Error::ChecksumMismatch { expected, found }
+            }
+            Framing::TrailingData(n) => Error::TrailingData(n),
+        }
+    }
+}
+
+impl From<binary::Error> for Error {
+    fn from(e: binary::Error) -> Self {
+        Error::Network(e)
+    }
+}
+
+/// Progress of a training run besides the network itself.
+#[derive(Debug, Clone)]
+pub struct TrainingState {
+    /// Number of training steps taken so far
+    pub step: u64,
+    pub epoch: u64,
+    /// Random number generator driving sampling, shuffling and augmentation
+    pub rng: ChaCha8Rng,
+}
+
+impl TrainingState {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            step: 0,
+            epoch: 0,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+pub fn to_bytes(nn: &NeuralNetwork, state: &TrainingState) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&state.step.to_le_bytes());
+    buf.extend_from_slice(&state.epoch.to_le_bytes());
+    buf.extend_from_slice(&state.rng.get_seed());
+    buf.extend_from_slice(&state.rng.get_stream().to_le_bytes());
+    buf.extend_from_slice(&state.rng.get_word_pos().to_le_bytes());
+
+    let mut network = Vec::new();
+    binary::write(nn, &mut network)?;
+    buf.extend_from_slice(&(network.len() as u64).to_le_bytes());
+    buf.extend_from_slice(&network);
+
+    buf.extend_from_slice(&(nn.accumulated() as u64).to_le_bytes());
+    for layer in nn.layers() {
+        for v in layer.weights_grad.to_vec() {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in layer.bias_grad.to_vec() {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    let checksum = crc32(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+    Ok(buf)
+}
+
+pub fn from_bytes(buf: &[u8]) -> Result<(NeuralNetwork, TrainingState), Error> {
+    let mut cursor = Cursor::open(buf, MAGIC, VERSION)?;
+    let step = cursor.u64()?;
+    let epoch = cursor.u64()?;
+    let mut rng = ChaCha8Rng::from_seed(cursor.array()?);
+    rng.set_stream(cursor.u64()?);
+    rng.set_word_pos(u128::from_le_bytes(cursor.array()?));
+
+    let network_len = cursor.u64()? as usize;
+    let mut nn = binary::read(&mut cursor.take(network_len)?)?;
+
+    let accumulated = cursor.u64()? as usize;
+    let mut grads = Vec::new();
+    for layer in nn.layers() {
+        let (rows, columns) = layer.weights.size();
+        let weights_grad = cursor.f32s(rows * columns)?;
+        let bias_grad = cursor.f32s(rows)?;
+        grads.push((
+            Matrix::from_slice_cm(&weights_grad, rows, columns),
+            Matrix::from_slice_cm(&bias_grad, rows, 1),
+        ));
+    }
+
+    cursor.finish()?;
+
+    for (layer, (weights_grad, bias_grad)) in nn.layers_mut().iter_mut().zip(grads) {
+        layer.weights_grad = weights_grad;
+        layer.bias_grad = bias_grad;
+    }
+    nn.set_accumulated(accumulated);
+
+    Ok((nn, TrainingState { step, epoch, rng }))
+}
+
+/// Writes a checkpoint to `path`. The file is replaced atomically so an
+/// interrupted save never leaves a half written checkpoint behind.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    nn: &NeuralNetwork,
+    state: &TrainingState,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(&to_bytes(nn, state)?)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Restores the network and training state saved by [`save`].
+pub fn resume_from<P: AsRef<Path>>(path: P) -> Result<(NeuralNetwork, TrainingState), Error> {
+    from_bytes(&fs::read(path)?)
+}
+
+/// Saves checkpoints named `checkpoint-{step}.ckpt` into a directory,
+/// keeping only the most recent ones.
+#[derive(Debug, Clone)]
+pub struct CheckpointManager {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl CheckpointManager {
+    /// Keeps the last `keep` checkpoints in `dir`, which is created if needed.
+    pub fn new<P: AsRef<Path>>(dir: P, keep: usize) -> Result<Self, Error> {
+        assert!(keep > 0);
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            keep,
+        })
+    }
+
+    /// Checkpoints in the directory, oldest first.
+    pub fn checkpoints(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut checkpoints = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let step = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("checkpoint-"))
+                .and_then(|name| name.strip_suffix(".ckpt"))
+                .and_then(|step| step.parse::<u64>().ok());
+            if let Some(step) = step {
+                checkpoints.push((step, path));
+            }
+        }
+        checkpoints.sort();
+        Ok(checkpoints.into_iter().map(|(_, path)| path).collect())
+    }
+
+    pub fn latest(&self) -> Result<Option<PathBuf>, Error> {
+        Ok(self.checkpoints()?.pop())
+    }
+
+    /// Saves a checkpoint for the current step and removes the oldest ones
+    /// beyond the number to keep.
+    pub fn save(&self, nn: &NeuralNetwork, state: &TrainingState) -> Result<PathBuf, Error> {
+        let path = self.dir.join(format!("checkpoint-{}.ckpt", state.step));
+        save(&path, nn, state)?;
+
+        let checkpoints = self.checkpoints()?;
+        if checkpoints.len() > self.keep {
+            for old in &checkpoints[..checkpoints.len() - self.keep] {
+                fs::remove_file(old)?;
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Resumes from the most recent checkpoint, if there is one.
+    pub fn resume(&self) -> Result<Option<(NeuralNetwork, TrainingState)>, Error> {
+        match self.latest()? {
+            Some(path) => resume_from(path).map(Some),
+            None => Ok(None),
+        }
+    }
+}
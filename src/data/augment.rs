@@ -0,0 +1,356 @@
+//! Random image augmentations applied while iterating over a dataset.
+//!
+//! Images are flat inputs in the channel-planar layout of the MNIST and
+//! CIFAR loaders: every channel is a plane of `height * width` values stored
+//! row by row, one plane after another. Geometric augmentations sample the
+//! source image with bilinear interpolation and fill pixels coming from
+//! outside of it with the background value.
+//!
+//! An [`Augmenter`] chains augmentations and is attached to a
+//! [`super::DataLoader`] with [`super::DataLoader::augment`]. Every sample
+//! draws from its own random number generator derived from the augmenter's
+//! seed, the epoch and the sample's position in the epoch, so an epoch is
+//! augmented identically no matter how its batches are produced.
+
+use std::fmt::Debug;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::synthetic::normal;
+
+/// Dimensions of the images of a dataset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageShape {
+    pub channels: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl ImageShape {
+    pub fn new(channels: usize, height: usize, width: usize) -> Self {
+        assert!(channels > 0 && height > 0 && width > 0);
+        Self {
+            channels,
+            height,
+            width,
+        }
+    }
+
+    /// Number of values of one image.
+    pub fn len(&self) -> usize {
+        self.channels * self.height * self.width
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// An image being augmented.
+#[derive(Debug)]
+pub struct Image<'a> {
+    pub data: &'a mut [f32],
+    pub shape: ImageShape,
+    /// Value of pixels outside the image, e.g. the normalized value of black
+    pub background: f32,
+}
+
+impl Image<'_> {
+    /// Replaces every pixel `(x, y)` by the source image sampled at
+    /// `source(x, y)`.
+    pub fn warp<F: Fn(f32, f32) -> (f32, f32)>(&mut self, source: F) {
+        let ImageShape {
+            channels,
+            height,
+            width,
+        } = self.shape;
+        let plane = height * width;
+        let original = self.data.to_vec();
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = source(x as f32, y as f32);
+                for c in 0..channels {
+                    self.data[c * plane + y * width + x] =
+                        self.sample(&original[c * plane..(c + 1) * plane], sx, sy);
+                }
+            }
+        }
+    }
+
+    /// Bilinear interpolation of one channel plane.
+    fn sample(&self, plane: &[f32], x: f32, y: f32) -> f32 {
+        let (width, height) = (self.shape.width as isize, self.shape.height as isize);
+        let pixel = |px: isize, py: isize| {
+            if px < 0 || py < 0 || px >= width || py >= height {
+                self.background
+            } else {
+                plane[(py * width + px) as usize]
+            }
+        };
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        let top = pixel(x0, y0) * (1.0 - fx) + pixel(x0 + 1, y0) * fx;
+        let bottom = pixel(x0, y0 + 1) * (1.0 - fx) + pixel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn center(&self) -> (f32, f32) {
+        (
+            (self.shape.width - 1) as f32 / 2.0,
+            (self.shape.height - 1) as f32 / 2.0,
+        )
+    }
+}
+
+/// A random transformation of an image.
+pub trait Augmentation: Debug + Send + Sync {
+    fn apply(&self, image: &mut Image<'_>, rng: &mut ChaCha8Rng);
+}
+
+/// Shifts the image by up to the given number of whole pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Translate {
+    pub max_dx: usize,
+    pub max_dy: usize,
+}
+
+impl Augmentation for Translate {
+    fn apply(&self, image: &mut Image<'_>, rng: &mut ChaCha8Rng) {
+        let (max_dx, max_dy) = (self.max_dx as i32, self.max_dy as i32);
+        let dx = rng.gen_range(-max_dx..=max_dx) as f32;
+        let dy = rng.gen_range(-max_dy..=max_dy) as f32;
+        image.warp(|x, y| (x - dx, y - dy));
+    }
+}
+
+/// Rotates the image around its center by up to `max_degrees` either way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rotate {
+    pub max_degrees: f32,
+}
+
+impl Augmentation for Rotate {
+    fn apply(&self, image: &mut Image<'_>, rng: &mut ChaCha8Rng) {
+        let angle = rng
+            .gen_range(-self.max_degrees..=self.max_degrees)
+            .to_radians();
+        let (sin, cos) = angle.sin_cos();
+        let (cx, cy) = image.center();
+        image.warp(|x, y| {
+            let (dx, dy) = (x - cx, y - cy);
+            (cx + cos * dx + sin * dy, cy - sin * dx + cos * dy)
+        });
+    }
+}
+
+/// Zooms the image around its center by a factor in `min..=max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Augmentation for Scale {
+    fn apply(&self, image: &mut Image<'_>, rng: &mut ChaCha8Rng) {
+        let factor = rng.gen_range(self.min..=self.max);
+        let (cx, cy) = image.center();
+        image.warp(|x, y| (cx + (x - cx) / factor, cy + (y - cy) / factor));
+    }
+}
+
+/// Elastic distortion after Simard et al.: a random displacement field,
+/// smoothed with a Gaussian of standard deviation `sigma` and scaled by
+/// `alpha` pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Elastic {
+    pub alpha: f32,
+    pub sigma: f32,
+}
+
+impl Elastic {
+    /// Separable Gaussian blur of a `height * width` field.
+    fn smooth(&self, field: &[f32], width: usize, height: usize) -> Vec<f32> {
+        let radius = (3.0 * self.sigma).ceil() as isize;
+        let kernel = (-radius..=radius)
+            .map(|i| (-(i * i) as f32 / (2.0 * self.sigma * self.sigma)).exp())
+            .collect::<Vec<f32>>();
+        let sum = kernel.iter().sum::<f32>();
+        let kernel = kernel.iter().map(|k| k / sum).collect::<Vec<f32>>();
+
+        let blur = |field: &[f32], horizontal: bool| {
+            let mut out = vec![0.0; field.len()];
+            for y in 0..height as isize {
+                for x in 0..width as isize {
+                    let mut acc = 0.0;
+                    for (k, weight) in (-radius..=radius).zip(&kernel) {
+                        let (sx, sy) = if horizontal { (x + k, y) } else { (x, y + k) };
+                        if sx >= 0 && sy >= 0 && sx < width as isize && sy < height as isize {
+                            acc += weight * field[sy as usize * width + sx as usize];
+                        }
+                    }
+                    out[y as usize * width + x as usize] = acc;
+                }
+            }
+            out
+        };
+        blur(&blur(field, true), false)
+    }
+}
+
+impl Augmentation for Elastic {
+    fn apply(&self, image: &mut Image<'_>, rng: &mut ChaCha8Rng) {
+        let (width, height) = (image.shape.width, image.shape.height);
+        let mut field = || {
+            let noise = (0..width * height)
+                .map(|_| rng.gen_range(-1.0..=1.0))
+                .collect::<Vec<f32>>();
+            self.smooth(&noise, width, height)
+        };
+        let (dx, dy) = (field(), field());
+        image.warp(|x, y| {
+            let i = y as usize * width + x as usize;
+            (x + self.alpha * dx[i], y + self.alpha * dy[i])
+        });
+    }
+}
+
+/// Adds Gaussian noise of standard deviation `std` to every value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaussianNoise {
+    pub std: f32,
+}
+
+impl Augmentation for GaussianNoise {
+    fn apply(&self, image: &mut Image<'_>, rng: &mut ChaCha8Rng) {
+        for v in image.data.iter_mut() {
+            *v += self.std * normal(rng);
+        }
+    }
+}
+
+/// With `probability`, sets a random rectangle covering `min_area..=max_area`
+/// of the image to the background value in every channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RandomErasing {
+    pub probability: f32,
+    pub min_area: f32,
+    pub max_area: f32,
+}
+
+impl Augmentation for RandomErasing {
+    fn apply(&self, image: &mut Image<'_>, rng: &mut ChaCha8Rng) {
+        if !rng.gen_bool(self.probability as f64) {
+            return;
+        }
+        let ImageShape {
+            channels,
+            height,
+            width,
+        } = image.shape;
+        let area = rng.gen_range(self.min_area..=self.max_area) * (width * height) as f32;
+        let aspect = rng.gen_range(0.3f32..=3.3);
+        let h = ((area * aspect).sqrt().round() as usize).clamp(1, height);
+        let w = ((area / aspect).sqrt().round() as usize).clamp(1, width);
+        let top = rng.gen_range(0..=height - h);
+        let left = rng.gen_range(0..=width - w);
+        for c in 0..channels {
+            for y in top..top + h {
+                let row = c * height * width + y * width;
+                image.data[row + left..row + left + w].fill(image.background);
+            }
+        }
+    }
+}
+
+/// Mirrors the image left to right with `probability`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HorizontalFlip {
+    pub probability: f32,
+}
+
+impl Augmentation for HorizontalFlip {
+    fn apply(&self, image: &mut Image<'_>, rng: &mut ChaCha8Rng) {
+        if rng.gen_bool(self.probability as f64) {
+            for row in image.data.chunks_exact_mut(image.shape.width) {
+                row.reverse();
+            }
+        }
+    }
+}
+
+/// A seeded chain of augmentations applied in order.
+///
+/// ```no_run
+/// use neural::data::{
+///     augment::{Augmenter, Elastic, ImageShape, Rotate, Translate},
+///     mnist::{MnistLoader, Split},
+///     DataLoader,
+/// };
+///
+/// let training = MnistLoader::new("./data/mnist").load(Split::Train).unwrap();
+/// let augmenter = Augmenter::new(ImageShape::new(1, 28, 28), 42)
+///     .then(Rotate { max_degrees: 10.0 })
+///     .then(Translate { max_dx: 2, max_dy: 2 })
+///     .then(Elastic { alpha: 34.0, sigma: 4.0 });
+/// let mut loader = DataLoader::new(&training, 64).shuffle(7).augment(&augmenter);
+/// for batch in loader.next_epoch() {
+///     // batch.inputs holds freshly augmented images
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Augmenter {
+    shape: ImageShape,
+    seed: u64,
+    background: f32,
+    steps: Vec<Box<dyn Augmentation>>,
+}
+
+impl Augmenter {
+    pub fn new(shape: ImageShape, seed: u64) -> Self {
+        Self {
+            shape,
+            seed,
+            background: 0.0,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Value filled in outside of the source image, `0.0` by default.
+    pub fn background(mut self, background: f32) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn then<A: Augmentation + 'static>(mut self, augmentation: A) -> Self {
+        self.steps.push(Box::new(augmentation));
+        self
+    }
+
+    pub fn shape(&self) -> ImageShape {
+        self.shape
+    }
+
+    /// Applies every augmentation to `image` using `rng`.
+    pub fn apply(&self, image: &mut [f32], rng: &mut ChaCha8Rng) {
+        assert_eq!(image.len(), self.shape.len());
+        let mut image = Image {
+            data: image,
+            shape: self.shape,
+            background: self.background,
+        };
+        for step in &self.steps {
+            step.apply(&mut image, rng);
+        }
+    }
+
+    /// Augments the sample at `position` of `epoch` the way a
+    /// [`super::DataLoader`] does.
+    pub fn apply_at(&self, image: &mut [f32], epoch: u64, position: usize) {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(epoch);
+        rng.set_word_pos((position as u128) << 32);
+        self.apply(image, &mut rng);
+    }
+}
@@ -0,0 +1,333 @@
+//! Loaders for the binary versions of CIFAR-10 and CIFAR-100.
+//!
+//! Every record of a batch file is one label byte (CIFAR-10) or a coarse and a
+//! fine label byte (CIFAR-100), followed by a 32x32 image as 3072 bytes: the
+//! red, green and blue planes one after another, each stored row by row. The
+//! planar layout is kept in the loaded inputs.
+//!
+//! Files are looked up in a local directory under their original names, i.e.
+//! the extracted `cifar-10-batches-bin` or `cifar-100-binary` directory.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{Dataset, InMemoryDataset};
+
+pub const ROWS: usize = 32;
+pub const COLUMNS: usize = 32;
+pub const CHANNELS: usize = 3;
+/// Number of bytes of one image.
+pub const IMAGE_LEN: usize = ROWS * COLUMNS * CHANNELS;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The batch file does not exist.
+    MissingFile(PathBuf),
+    /// The file length is not a multiple of the record length.
+    Truncated(PathBuf),
+    /// A label is out of range for the variant.
+    InvalidLabel {
+        label: u8,
+        classes: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::MissingFile(path) => write!(f, "missing file {}", path.display()),
+            Error::Truncated(path) => write!(f, "{} ends in a partial record", path.display()),
+            Error::InvalidLabel { label, classes } => {
+                write!(f, "label {label} is out of range for {classes} classes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    Train,
+    Test,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Cifar10,
+    Cifar100,
+}
+
+impl Variant {
+    /// Number of fine classes.
+    pub fn classes(&self) -> usize {
+        match self {
+            Variant::Cifar10 => 10,
+            Variant::Cifar100 => 100,
+        }
+    }
+
+    /// Bytes of one record, labels included.
+    fn record_len(&self) -> usize {
+        match self {
+            Variant::Cifar10 => 1 + IMAGE_LEN,
+            Variant::Cifar100 => 2 + IMAGE_LEN,
+        }
+    }
+
+    fn file_names(&self, split: Split) -> Vec<String> {
+        match (self, split) {
+            (Variant::Cifar10, Split::Train) => {
+                (1..=5).map(|i| format!("data_batch_{i}.bin")).collect()
+            }
+            (Variant::Cifar10, Split::Test) => vec!["test_batch.bin".to_string()],
+            (Variant::Cifar100, Split::Train) => vec!["train.bin".to_string()],
+            (Variant::Cifar100, Split::Test) => vec!["test.bin".to_string()],
+        }
+    }
+}
+
+/// Which CIFAR-100 labels become the targets. CIFAR-10 only has fine labels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelKind {
+    /// The 100 classes of CIFAR-100 or the 10 classes of CIFAR-10
+    Fine,
+    /// The 20 superclasses of CIFAR-100
+    Coarse,
+}
+
+/// How pixel values are mapped to network inputs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Raw pixel values in `0..=255`
+    None,
+    /// Pixel values divided by 255 into `0..=1`
+    UnitRange,
+    /// `(pixel / 255 - mean[channel]) / std[channel]`
+    Standardize { mean: [f32; 3], std: [f32; 3] },
+}
+
+impl Normalization {
+    /// Channel means and standard deviations of the CIFAR-10 training images.
+    pub const CIFAR10: Normalization = Normalization::Standardize {
+        mean: [0.4914, 0.4822, 0.4465],
+        std: [0.2470, 0.2435, 0.2616],
+    };
+    /// Channel means and standard deviations of the CIFAR-100 training images.
+    pub const CIFAR100: Normalization = Normalization::Standardize {
+        mean: [0.5071, 0.4865, 0.4409],
+        std: [0.2673, 0.2564, 0.2762],
+    };
+
+    fn apply(&self, channel: usize, pixel: u8) -> f32 {
+        match self {
+            Normalization::None => pixel as f32,
+            Normalization::UnitRange => pixel as f32 / 255.0,
+            Normalization::Standardize { mean, std } => {
+                (pixel as f32 / 255.0 - mean[channel]) / std[channel]
+            }
+        }
+    }
+}
+
+/// Channel-planar color images with one-hot labels.
+#[derive(Debug, Clone)]
+pub struct CifarDataset {
+    samples: InMemoryDataset,
+    fine_labels: Vec<usize>,
+    coarse_labels: Option<Vec<usize>>,
+    label_kind: LabelKind,
+}
+
+impl CifarDataset {
+    /// Class index of every image, of the kind used for the targets.
+    pub fn labels(&self) -> &[usize] {
+        match (self.label_kind, &self.coarse_labels) {
+            (LabelKind::Coarse, Some(coarse)) => coarse,
+            _ => &self.fine_labels,
+        }
+    }
+
+    pub fn label(&self, index: usize) -> usize {
+        self.labels()[index]
+    }
+
+    pub fn fine_labels(&self) -> &[usize] {
+        &self.fine_labels
+    }
+
+    /// Superclass of every image, only present for CIFAR-100.
+    pub fn coarse_labels(&self) -> Option<&[usize]> {
+        self.coarse_labels.as_deref()
+    }
+
+    /// Mean and standard deviation of every channel over all images, `None`
+    /// if there are no images.
+    ///
+    /// Computed on the normalized inputs, so on a dataset loaded with
+    /// [`Normalization::UnitRange`] the result can be passed to
+    /// [`Normalization::Standardize`].
+    pub fn channel_stats(&self) -> Option<([f32; 3], [f32; 3])> {
+        if self.is_empty() {
+            return None;
+        }
+        let plane = ROWS * COLUMNS;
+        let count = (self.len() * plane) as f64;
+        let mut mean = [0.0; 3];
+        let mut std = [0.0; 3];
+        for channel in 0..CHANNELS {
+            let (mut sum, mut sum_sq) = (0.0f64, 0.0f64);
+            for index in 0..self.len() {
+                for v in &self.input(index)[channel * plane..(channel + 1) * plane] {
+                    sum += *v as f64;
+                    sum_sq += (*v as f64) * (*v as f64);
+                }
+            }
+            let m = sum / count;
+            mean[channel] = m as f32;
+            std[channel] = (sum_sq / count - m * m).max(0.0).sqrt() as f32;
+        }
+        Some((mean, std))
+    }
+}
+
+delegate_dataset!(CifarDataset);
+
+/// Loads CIFAR-10 or CIFAR-100 from a directory.
+///
+/// ```no_run
+/// use neural::data::cifar::{CifarLoader, LabelKind, Normalization, Split, Variant};
+///
+/// let training = CifarLoader::new("./data/cifar-100-binary")
+///     .variant(Variant::Cifar100)
+///     .labels(LabelKind::Coarse)
+///     .normalization(Normalization::CIFAR100)
+///     .load(Split::Train)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CifarLoader {
+    dir: PathBuf,
+    variant: Variant,
+    label_kind: LabelKind,
+    normalization: Normalization,
+}
+
+impl CifarLoader {
+    /// Loads CIFAR-10 with pixels scaled into `0..=1` unless configured otherwise.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            variant: Variant::Cifar10,
+            label_kind: LabelKind::Fine,
+            normalization: Normalization::UnitRange,
+        }
+    }
+
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Coarse labels fall back to fine labels for CIFAR-10.
+    pub fn labels(mut self, label_kind: LabelKind) -> Self {
+        self.label_kind = label_kind;
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    pub fn load(&self, split: Split) -> Result<CifarDataset, Error> {
+        let paths = self
+            .variant
+            .file_names(split)
+            .into_iter()
+            .map(|name| self.dir.join(name))
+            .collect::<Vec<PathBuf>>();
+        if let Some(missing) = paths.iter().find(|path| !path.exists()) {
+            return Err(Error::MissingFile(missing.clone()));
+        }
+        self.load_files(&paths)
+    }
+
+    /// Loads the records of the given batch files in order.
+    pub fn load_files<P: AsRef<Path>>(&self, paths: &[P]) -> Result<CifarDataset, Error> {
+        let record_len = self.variant.record_len();
+        let mut records = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let bytes = fs::read(path)?;
+            if bytes.len() % record_len != 0 {
+                return Err(Error::Truncated(path.to_path_buf()));
+            }
+            records.extend_from_slice(&bytes);
+        }
+
+        let count = records.len() / record_len;
+        let mut inputs = Vec::with_capacity(count * IMAGE_LEN);
+        let mut fine_labels = Vec::with_capacity(count);
+        let mut coarse_labels = Vec::with_capacity(count);
+        let plane = ROWS * COLUMNS;
+        for record in records.chunks_exact(record_len) {
+            let (labels, image) = record.split_at(record_len - IMAGE_LEN);
+            match self.variant {
+                Variant::Cifar10 => fine_labels.push(check_label(labels[0], 10)?),
+                Variant::Cifar100 => {
+                    coarse_labels.push(check_label(labels[0], 20)?);
+                    fine_labels.push(check_label(labels[1], 100)?);
+                }
+            }
+            for (i, pixel) in image.iter().enumerate() {
+                inputs.push(self.normalization.apply(i / plane, *pixel));
+            }
+        }
+
+        let coarse_labels = match self.variant {
+            Variant::Cifar10 => None,
+            Variant::Cifar100 => Some(coarse_labels),
+        };
+        let (labels, classes) = match (self.label_kind, &coarse_labels) {
+            (LabelKind::Coarse, Some(coarse)) => (coarse, 20),
+            _ => (&fine_labels, self.variant.classes()),
+        };
+        let mut targets = vec![0.0; count * classes];
+        for (index, label) in labels.iter().enumerate() {
+            targets[index * classes + label] = 1.0;
+        }
+
+        Ok(CifarDataset {
+            samples: InMemoryDataset::new(inputs, targets, IMAGE_LEN, classes),
+            fine_labels,
+            coarse_labels,
+            label_kind: self.label_kind,
+        })
+    }
+}
+
+fn check_label(label: u8, classes: usize) -> Result<usize, Error> {
+    if (label as usize) < classes {
+        Ok(label as usize)
+    } else {
+        Err(Error::InvalidLabel { label, classes })
+    }
+}
@@ -0,0 +1,567 @@
+//! Loader for tabular data in CSV files.
+//!
+//! Fields may be quoted with `"`, quotes inside quoted fields are doubled and
+//! quoted fields may span lines. Numeric columns become one input or target
+//! value each, categorical columns one value per category (one-hot).
+
+use std::{
+    collections::BTreeSet,
+    fmt, fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use super::InMemoryDataset;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The input holds no records.
+    Empty,
+    /// A quoted field is not closed before the end of the input.
+    UnterminatedQuote {
+        line: usize,
+    },
+    /// A record has a different number of fields than the first one.
+    FieldCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A column name or index that is not in the file.
+    UnknownColumn(String),
+    /// The selected features or targets encode to no values at all.
+    NoValues,
+    /// A numeric column holds something that is not a number.
+    Parse {
+        line: usize,
+        column: String,
+        value: String,
+    },
+    /// A value is missing and [`MissingValues::Error`] is used.
+    MissingValue {
+        line: usize,
+        column: String,
+    },
+    /// A categorical column holds a value outside its fixed categories.
+    UnknownCategory {
+        line: usize,
+        column: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Empty => write!(f, "no records"),
+            Error::UnterminatedQuote { line } => {
+                write!(f, "line {line}: quoted field is never closed")
+            }
+            Error::FieldCount {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: expected {expected} fields, found {found}"),
+            Error::UnknownColumn(column) => write!(f, "unknown column {column}"),
+            Error::NoValues => write!(f, "no feature or target values selected"),
+            Error::Parse {
+                line,
+                column,
+                value,
+            } => write!(f, "line {line}: column {column}: {value:?} is not a number"),
+            Error::MissingValue { line, column } => {
+                write!(f, "line {line}: column {column}: missing value")
+            }
+            Error::UnknownCategory {
+                line,
+                column,
+                value,
+            } => write!(
+                f,
+                "line {line}: column {column}: unknown category {value:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// A column selected by its header name or by its zero-based position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Name(String),
+    Index(usize),
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Self {
+        Column::Name(name.to_string())
+    }
+}
+
+impl From<String> for Column {
+    fn from(name: String) -> Self {
+        Column::Name(name)
+    }
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+/// What to do with empty fields and fields matching a missing-value marker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MissingValues {
+    /// Fail with [`Error::MissingValue`]
+    Error,
+    /// Drop records with a missing value in a selected column
+    Skip,
+    /// Use a fixed value. Categorical columns get all zeros.
+    Fill(f32),
+    /// Use the mean of the column. Categorical columns get all zeros.
+    Mean,
+}
+
+/// Name and encoding of a selected column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// Categories in the order of their one-hot positions, `None` for
+    /// numeric columns.
+    pub categories: Option<Vec<String>>,
+}
+
+impl ColumnInfo {
+    /// Number of values the column takes up in an input or target vector.
+    pub fn width(&self) -> usize {
+        self.categories.as_ref().map_or(1, |c| c.len())
+    }
+}
+
+/// Encoded records.
+#[derive(Debug, Clone)]
+pub struct CsvDataset {
+    samples: InMemoryDataset,
+    features: Vec<ColumnInfo>,
+    targets: Vec<ColumnInfo>,
+}
+
+impl CsvDataset {
+    /// Feature columns in the order they appear in every input.
+    pub fn features(&self) -> &[ColumnInfo] {
+        &self.features
+    }
+
+    /// Target columns in the order they appear in every target.
+    pub fn targets(&self) -> &[ColumnInfo] {
+        &self.targets
+    }
+
+    pub fn samples(&self) -> &InMemoryDataset {
+        &self.samples
+    }
+}
+
+delegate_dataset!(CsvDataset);
+
+/// Reads CSV files into datasets.
+///
+/// By default the last column is the target and every other column a
+/// feature, and whether the first record is a header is detected: it is
+/// taken as one if none of its fields is empty or a number while the second
+/// record holds at least one number.
+///
+/// ```no_run
+/// use neural::data::csv::{CsvLoader, MissingValues};
+///
+/// let dataset = CsvLoader::new()
+///     .features(["age", "income", "city"])
+///     .targets(["churned"])
+///     .categorical(["city"])
+///     .missing(MissingValues::Mean)
+///     .load("customers.csv")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CsvLoader {
+    delimiter: char,
+    header: Option<bool>,
+    features: Option<Vec<Column>>,
+    targets: Option<Vec<Column>>,
+    categorical: Vec<(Column, Option<Vec<String>>)>,
+    missing: MissingValues,
+    missing_markers: Vec<String>,
+}
+
+impl Default for CsvLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A column of the file as read, before encoding.
+enum Values {
+    Numeric(Vec<Option<f32>>),
+    Categorical(Vec<Option<String>>),
+}
+
+impl CsvLoader {
+    pub fn new() -> Self {
+        Self {
+            delimiter: ',',
+            header: None,
+            features: None,
+            targets: None,
+            categorical: Vec::new(),
+            missing: MissingValues::Error,
+            missing_markers: ["NA", "N/A", "NaN", "null", "?"]
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Overrides header detection.
+    pub fn has_header(mut self, header: bool) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    pub fn features<C: Into<Column>, I: IntoIterator<Item = C>>(mut self, columns: I) -> Self {
+        self.features = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn targets<C: Into<Column>, I: IntoIterator<Item = C>>(mut self, columns: I) -> Self {
+        self.targets = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// One-hot encodes the columns, with categories sorted as strings.
+    pub fn categorical<C: Into<Column>, I: IntoIterator<Item = C>>(mut self, columns: I) -> Self {
+        self.categorical
+            .extend(columns.into_iter().map(|c| (c.into(), None)));
+        self
+    }
+
+    /// One-hot encodes the column with fixed categories, e.g. those of the
+    /// training set when loading the test set.
+    pub fn categories<C: Into<Column>, S: ToString>(mut self, column: C, categories: &[S]) -> Self {
+        let categories = categories.iter().map(|c| c.to_string()).collect();
+        self.categorical.push((column.into(), Some(categories)));
+        self
+    }
+
+    pub fn missing(mut self, missing: MissingValues) -> Self {
+        self.missing = missing;
+        self
+    }
+
+    /// Field contents that count as missing besides the empty field.
+    pub fn missing_markers<S: ToString>(mut self, markers: &[S]) -> Self {
+        self.missing_markers = markers.iter().map(|m| m.to_string()).collect();
+        self
+    }
+
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<CsvDataset, Error> {
+        self.parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn read<R: Read>(&self, mut reader: R) -> Result<CsvDataset, Error> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        self.parse(&text)
+    }
+
+    pub fn parse(&self, text: &str) -> Result<CsvDataset, Error> {
+        let mut records = records(text, self.delimiter)?;
+        if records.is_empty() {
+            return Err(Error::Empty);
+        }
+        let width = records[0].1.len();
+        for (line, fields) in &records {
+            if fields.len() != width {
+                return Err(Error::FieldCount {
+                    line: *line,
+                    expected: width,
+                    found: fields.len(),
+                });
+            }
+        }
+
+        let header = match self.header {
+            Some(header) => header,
+            None => self.detect_header(&records),
+        };
+        let names = if header {
+            records.remove(0).1
+        } else {
+            (0..width).map(|i| i.to_string()).collect()
+        };
+        let resolve = |column: &Column| match column {
+            Column::Name(name) if header => names
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| Error::UnknownColumn(name.clone())),
+            Column::Name(name) => Err(Error::UnknownColumn(name.clone())),
+            Column::Index(index) if *index < width => Ok(*index),
+            Column::Index(index) => Err(Error::UnknownColumn(index.to_string())),
+        };
+
+        let targets = match &self.targets {
+            Some(columns) => columns.iter().map(resolve).collect::<Result<Vec<_>, _>>()?,
+            None => vec![width - 1],
+        };
+        let features = match &self.features {
+            Some(columns) => columns.iter().map(resolve).collect::<Result<Vec<_>, _>>()?,
+            None => (0..width).filter(|i| !targets.contains(i)).collect(),
+        };
+        let mut categorical = vec![None; width];
+        for (column, categories) in &self.categorical {
+            categorical[resolve(column)?] = Some(categories.clone());
+        }
+
+        // Read the selected columns, dropping records with missing values
+        // first when asked to
+        let selected = features
+            .iter()
+            .chain(&targets)
+            .copied()
+            .collect::<BTreeSet<usize>>();
+        if self.missing == MissingValues::Skip {
+            records.retain(|(_, fields)| selected.iter().all(|i| !self.is_missing(&fields[*i])));
+        }
+        let mut values = Vec::with_capacity(width);
+        for column in 0..width {
+            if !selected.contains(&column) {
+                values.push(None);
+                continue;
+            }
+            let mut column_values = match categorical[column] {
+                Some(_) => Values::Categorical(Vec::with_capacity(records.len())),
+                None => Values::Numeric(Vec::with_capacity(records.len())),
+            };
+            for (line, fields) in &records {
+                let field = fields[column].trim();
+                if self.is_missing(field) {
+                    if self.missing == MissingValues::Error {
+                        return Err(Error::MissingValue {
+                            line: *line,
+                            column: names[column].clone(),
+                        });
+                    }
+                    match &mut column_values {
+                        Values::Numeric(v) => v.push(None),
+                        Values::Categorical(v) => v.push(None),
+                    }
+                    continue;
+                }
+                match &mut column_values {
+                    Values::Numeric(v) => {
+                        v.push(Some(field.parse::<f32>().map_err(|_| Error::Parse {
+                            line: *line,
+                            column: names[column].clone(),
+                            value: field.to_string(),
+                        })?))
+                    }
+                    Values::Categorical(v) => v.push(Some(field.to_string())),
+                }
+            }
+            values.push(Some(column_values));
+        }
+
+        // Fix the encoding of every selected column
+        let mut infos = vec![None; width];
+        let mut fills = vec![0.0; width];
+        for column in selected.iter().copied() {
+            let categories = match &values[column] {
+                Some(Values::Categorical(v)) => Some(match &categorical[column] {
+                    Some(Some(fixed)) => {
+                        for ((line, _), value) in records.iter().zip(v) {
+                            if let Some(value) = value.as_ref().filter(|v| !fixed.contains(v)) {
+                                return Err(Error::UnknownCategory {
+                                    line: *line,
+                                    column: names[column].clone(),
+                                    value: value.clone(),
+                                });
+                            }
+                        }
+                        fixed.clone()
+                    }
+                    _ => v
+                        .iter()
+                        .flatten()
+                        .cloned()
+                        .collect::<BTreeSet<String>>()
+                        .into_iter()
+                        .collect(),
+                }),
+                Some(Values::Numeric(v)) => {
+                    fills[column] = match self.missing {
+                        MissingValues::Fill(value) => value,
+                        MissingValues::Mean => {
+                            let present = v.iter().flatten().collect::<Vec<_>>();
+                            if present.is_empty() {
+                                0.0
+                            } else {
+                                present.iter().copied().sum::<f32>() / present.len() as f32
+                            }
+                        }
+                        _ => 0.0,
+                    };
+                    None
+                }
+                None => unreachable!(),
+            };
+            infos[column] = Some(ColumnInfo {
+                name: names[column].clone(),
+                categories,
+            });
+        }
+
+        let encode = |columns: &[usize], record: usize, out: &mut Vec<f32>| {
+            for column in columns {
+                match &values[*column] {
+                    Some(Values::Numeric(v)) => out.push(v[record].unwrap_or(fills[*column])),
+                    Some(Values::Categorical(v)) => {
+                        let categories = infos[*column]
+                            .as_ref()
+                            .and_then(|i| i.categories.as_ref())
+                            .unwrap();
+                        let start = out.len();
+                        out.resize(start + categories.len(), 0.0);
+                        if let Some(value) = &v[record] {
+                            let position = categories.iter().position(|c| c == value).unwrap();
+                            out[start + position] = 1.0;
+                        }
+                    }
+                    None => unreachable!(),
+                }
+            }
+        };
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for record in 0..records.len() {
+            encode(&features, record, &mut inputs);
+            encode(&targets, record, &mut outputs);
+        }
+
+        let info = |columns: &[usize]| {
+            columns
+                .iter()
+                .map(|c| infos[*c].clone().unwrap())
+                .collect::<Vec<ColumnInfo>>()
+        };
+        let features = info(&features);
+        let targets = info(&targets);
+        let input_len = features.iter().map(ColumnInfo::width).sum::<usize>();
+        let target_len = targets.iter().map(ColumnInfo::width).sum::<usize>();
+        if input_len == 0 || target_len == 0 {
+            return Err(Error::NoValues);
+        }
+
+        Ok(CsvDataset {
+            samples: InMemoryDataset::new(inputs, outputs, input_len, target_len),
+            features,
+            targets,
+        })
+    }
+
+    fn is_missing(&self, field: &str) -> bool {
+        let field = field.trim();
+        field.is_empty() || self.missing_markers.iter().any(|m| m == field)
+    }
+
+    fn is_number(&self, field: &str) -> bool {
+        !self.is_missing(field) && field.trim().parse::<f32>().is_ok()
+    }
+
+    fn detect_header(&self, records: &[(usize, Vec<String>)]) -> bool {
+        let first = &records[0].1;
+        if first
+            .iter()
+            .any(|f| f.trim().is_empty() || self.is_number(f))
+        {
+            return false;
+        }
+        match records.get(1) {
+            Some((_, second)) => second.iter().any(|f| self.is_number(f)),
+            None => true,
+        }
+    }
+}
+
+/// Splits the input into records, each with the line it starts on.
+fn records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, Error> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                // Blank lines are skipped
+                if fields.len() > 1 || !fields[0].trim().is_empty() {
+                    records.push((start, std::mem::take(&mut fields)));
+                } else {
+                    fields.clear();
+                }
+                line += 1;
+                start = line;
+            }
+            c if c == delimiter => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::UnterminatedQuote { line: start });
+    }
+    fields.push(field);
+    if fields.len() > 1 || !fields[0].trim().is_empty() {
+        records.push((start, fields));
+    }
+    Ok(records)
+}
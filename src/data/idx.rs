@@ -0,0 +1,260 @@
+//! IDX files, the format of the MNIST family of datasets.
+//!
+//! ```text
+//! magic       2 zero bytes, 1 byte type code, 1 byte dimension count
+//! dimensions  u32 each, big-endian
+//! data        big-endian values of the type, last dimension varying fastest
+//! ```
+//!
+//! Gzip compressed files, as distributed by most mirrors, are decompressed
+//! transparently when the `gzip` feature is enabled.
+
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    BadMagic([u8; 4]),
+    UnknownType(u8),
+    /// The input ended before the announced amount of data was read.
+    Truncated,
+    /// The file is gzip compressed but the `gzip` feature is disabled.
+    GzipUnsupported,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::BadMagic(magic) => write!(f, "not an IDX file (magic number {magic:02x?})"),
+            Error::UnknownType(code) => write!(f, "unknown IDX type code {code:#04x}"),
+            Error::Truncated => write!(f, "file is truncated"),
+            Error::GzipUnsupported => {
+                write!(
+                    f,
+                    "file is gzip compressed, enable the `gzip` feature to read it"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Values of an IDX file in their stored type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data {
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+impl Data {
+    pub fn type_code(&self) -> u8 {
+        match self {
+            Data::U8(_) => 0x08,
+            Data::I8(_) => 0x09,
+            Data::I16(_) => 0x0B,
+            Data::I32(_) => 0x0C,
+            Data::F32(_) => 0x0D,
+            Data::F64(_) => 0x0E,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Data::U8(v) => v.len(),
+            Data::I8(v) => v.len(),
+            Data::I16(v) => v.len(),
+            Data::I32(v) => v.len(),
+            Data::F32(v) => v.len(),
+            Data::F64(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every value converted to `f32`.
+    pub fn to_f32(&self) -> Vec<f32> {
+        match self {
+            Data::U8(v) => v.iter().map(|v| *v as f32).collect(),
+            Data::I8(v) => v.iter().map(|v| *v as f32).collect(),
+            Data::I16(v) => v.iter().map(|v| *v as f32).collect(),
+            Data::I32(v) => v.iter().map(|v| *v as f32).collect(),
+            Data::F32(v) => v.clone(),
+            Data::F64(v) => v.iter().map(|v| *v as f32).collect(),
+        }
+    }
+}
+
+/// An IDX array: its dimensions and row-major values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdxArray {
+    pub dims: Vec<usize>,
+    pub data: Data,
+}
+
+impl IdxArray {
+    pub fn new(dims: Vec<usize>, data: Data) -> Self {
+        assert_eq!(dims.iter().product::<usize>(), data.len());
+
+        Self { dims, data }
+    }
+
+    /// Number of items along the first dimension, e.g. images in a file.
+    pub fn items(&self) -> usize {
+        self.dims.first().copied().unwrap_or(1)
+    }
+
+    /// Number of values per item, e.g. pixels per image.
+    pub fn item_len(&self) -> usize {
+        self.dims.iter().skip(1).product()
+    }
+}
+
+fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+    if !bytes.starts_with(&[0x1F, 0x8B]) {
+        return Ok(bytes);
+    }
+
+    #[cfg(feature = "gzip")]
+    {
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(&bytes[..])
+            .read_to_end(&mut decompressed)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => Error::Truncated,
+                _ => Error::Io(e),
+            })?;
+        Ok(decompressed)
+    }
+    #[cfg(not(feature = "gzip"))]
+    Err(Error::GzipUnsupported)
+}
+
+fn parse<T, const N: usize>(bytes: &[u8], from_be: fn([u8; N]) -> T) -> Vec<T> {
+    bytes
+        .chunks_exact(N)
+        .map(|b| from_be(b.try_into().unwrap()))
+        .collect()
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<IdxArray, Error> {
+    let magic: [u8; 4] = bytes.get(..4).ok_or(Error::Truncated)?.try_into().unwrap();
+    if magic[0] != 0 || magic[1] != 0 {
+        return Err(Error::BadMagic(magic));
+    }
+    let item_size: usize = match magic[2] {
+        0x08 | 0x09 => 1,
+        0x0B => 2,
+        0x0C | 0x0D => 4,
+        0x0E => 8,
+        code => return Err(Error::UnknownType(code)),
+    };
+
+    let ndims = magic[3] as usize;
+    let header_len = 4 + ndims * 4;
+    let dims = bytes
+        .get(4..header_len)
+        .ok_or(Error::Truncated)?
+        .chunks_exact(4)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+        .collect::<Vec<usize>>();
+
+    let data_len = dims
+        .iter()
+        .try_fold(item_size, |acc, dim| acc.checked_mul(*dim))
+        .ok_or(Error::Truncated)?;
+    let data_end = header_len.checked_add(data_len).ok_or(Error::Truncated)?;
+    let data = bytes.get(header_len..data_end).ok_or(Error::Truncated)?;
+
+    let data = match magic[2] {
+        0x08 => Data::U8(data.to_vec()),
+        0x09 => Data::I8(parse(data, i8::from_be_bytes)),
+        0x0B => Data::I16(parse(data, i16::from_be_bytes)),
+        0x0C => Data::I32(parse(data, i32::from_be_bytes)),
+        0x0D => Data::F32(parse(data, f32::from_be_bytes)),
+        _ => Data::F64(parse(data, f64::from_be_bytes)),
+    };
+
+    Ok(IdxArray { dims, data })
+}
+
+/// Reads an IDX file, decompressing it first if it is gzip compressed.
+pub fn read<R: Read>(reader: &mut R) -> Result<IdxArray, Error> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    from_bytes(&decompress(bytes)?)
+}
+
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<IdxArray, Error> {
+    from_bytes(&decompress(fs::read(path)?)?)
+}
+
+pub fn to_bytes(array: &IdxArray) -> Vec<u8> {
+    let mut buf = vec![0, 0, array.data.type_code(), array.dims.len() as u8];
+    for dim in &array.dims {
+        buf.extend_from_slice(&(*dim as u32).to_be_bytes());
+    }
+
+    match &array.data {
+        Data::U8(v) => buf.extend_from_slice(v),
+        Data::I8(v) => buf.extend(v.iter().flat_map(|v| v.to_be_bytes())),
+        Data::I16(v) => buf.extend(v.iter().flat_map(|v| v.to_be_bytes())),
+        Data::I32(v) => buf.extend(v.iter().flat_map(|v| v.to_be_bytes())),
+        Data::F32(v) => buf.extend(v.iter().flat_map(|v| v.to_be_bytes())),
+        Data::F64(v) => buf.extend(v.iter().flat_map(|v| v.to_be_bytes())),
+    }
+
+    buf
+}
+
+pub fn write<W: Write>(array: &IdxArray, writer: &mut W) -> Result<(), Error> {
+    writer.write_all(&to_bytes(array))?;
+    Ok(())
+}
+
+/// Writes an IDX file, gzip compressed if the path ends in `.gz` and the
+/// `gzip` feature is enabled.
+pub fn write_file<P: AsRef<Path>>(path: P, array: &IdxArray) -> Result<(), Error> {
+    let path = path.as_ref();
+    let gzip = path.extension().is_some_and(|ext| ext == "gz");
+
+    #[cfg(feature = "gzip")]
+    if gzip {
+        let mut encoder =
+            flate2::write::GzEncoder::new(fs::File::create(path)?, flate2::Compression::default());
+        write(array, &mut encoder)?;
+        encoder.finish()?;
+        return Ok(());
+    }
+    #[cfg(not(feature = "gzip"))]
+    if gzip {
+        return Err(Error::GzipUnsupported);
+    }
+
+    fs::write(path, to_bytes(array))?;
+    Ok(())
+}
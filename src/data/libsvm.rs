@@ -0,0 +1,333 @@
+//! Loader for the sparse LIBSVM / SVMlight text format.
+//!
+//! Every line is a record of the form
+//!
+//! ```text
+//! <label>[,<label>...] [qid:<query>] <index>:<value> <index>:<value> ... [# comment]
+//! ```
+//!
+//! with feature indices starting at 1 and strictly increasing. Several
+//! comma-separated labels make a multi-label record. Features that are not
+//! listed are zero.
+
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use super::{Dataset, InMemoryDataset};
+use crate::matrix::Matrix;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Densifying with zero features or zero classes.
+    Empty,
+    /// A line that does not follow the format.
+    Malformed {
+        line: usize,
+        message: String,
+    },
+    /// A feature index at or past the configured feature count.
+    IndexOutOfRange {
+        line: usize,
+        index: usize,
+        features: usize,
+    },
+    /// A label that is not a class index below the configured class count.
+    InvalidClass {
+        line: usize,
+        label: f32,
+        classes: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Empty => write!(f, "no features or no classes to densify into"),
+            Error::Malformed { line, message } => write!(f, "line {line}: {message}"),
+            Error::IndexOutOfRange {
+                line,
+                index,
+                features,
+            } => write!(
+                f,
+                "line {line}: feature index {index} is out of range for {features} features"
+            ),
+            Error::InvalidClass {
+                line,
+                label,
+                classes,
+            } => write!(
+                f,
+                "line {line}: label {label} is not a class index below {classes}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// One parsed line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Line the record was read from, starting at 1
+    pub line: usize,
+    pub labels: Vec<f32>,
+    pub qid: Option<u64>,
+    /// Zero-based feature indices with their values, in increasing order
+    pub features: Vec<(usize, f32)>,
+}
+
+/// Parses every record, skipping blank lines and comment lines.
+pub fn parse(text: &str) -> Result<Vec<Record>, Error> {
+    let mut records = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let malformed = |message: String| Error::Malformed {
+            line: line_number,
+            message,
+        };
+        let content = line.split('#').next().unwrap_or("").trim();
+        if content.is_empty() {
+            continue;
+        }
+        let mut tokens = content.split_whitespace();
+
+        let labels = tokens
+            .next()
+            .unwrap()
+            .split(',')
+            .map(|label| {
+                label
+                    .parse::<f32>()
+                    .map_err(|_| malformed(format!("invalid label {label:?}")))
+            })
+            .collect::<Result<Vec<f32>, Error>>()?;
+
+        let mut qid = None;
+        let mut features: Vec<(usize, f32)> = Vec::new();
+        for token in tokens {
+            let Some((key, value)) = token.split_once(':') else {
+                return Err(malformed(format!("expected index:value, found {token:?}")));
+            };
+            if key == "qid" {
+                if qid.is_some() || !features.is_empty() {
+                    return Err(malformed(
+                        "qid has to come right after the labels".to_string(),
+                    ));
+                }
+                qid = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| malformed(format!("invalid qid {value:?}")))?,
+                );
+                continue;
+            }
+            let index = key
+                .parse::<usize>()
+                .ok()
+                .filter(|index| *index > 0)
+                .ok_or_else(|| malformed(format!("invalid feature index {key:?}")))?;
+            let value = value
+                .parse::<f32>()
+                .map_err(|_| malformed(format!("invalid feature value {value:?}")))?;
+            if let Some((last, _)) = features.last() {
+                if index - 1 <= *last {
+                    return Err(malformed(format!(
+                        "feature index {index} does not follow {}",
+                        last + 1
+                    )));
+                }
+            }
+            features.push((index - 1, value));
+        }
+
+        records.push(Record {
+            line: line_number,
+            labels,
+            qid,
+            features,
+        });
+    }
+    Ok(records)
+}
+
+/// How the labels of a record become its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Targets {
+    /// The single label as is, for regression and `-1`/`+1` classification
+    Value,
+    /// Labels are class indices below `classes`, set in a multi-hot vector.
+    /// With one label per record this is one-hot encoding.
+    MultiHot { classes: usize },
+}
+
+/// Densified records.
+#[derive(Debug, Clone)]
+pub struct LibsvmDataset {
+    samples: InMemoryDataset,
+    qids: Vec<Option<u64>>,
+}
+
+impl LibsvmDataset {
+    /// Query id of every record.
+    pub fn qids(&self) -> &[Option<u64>] {
+        &self.qids
+    }
+
+    /// Inputs and targets with one column per record.
+    pub fn to_matrices(&self) -> (Matrix, Matrix) {
+        (
+            Matrix::from_slice_cm(self.samples.inputs(), self.input_len(), self.len()),
+            Matrix::from_slice_cm(self.samples.targets(), self.target_len(), self.len()),
+        )
+    }
+
+    pub fn samples(&self) -> &InMemoryDataset {
+        &self.samples
+    }
+}
+
+delegate_dataset!(LibsvmDataset);
+
+/// Reads LIBSVM files into dense datasets.
+///
+/// Without a configured feature count the highest index in the file decides
+/// it, so the training and test files of a benchmark should be loaded with
+/// the same explicit count.
+///
+/// ```no_run
+/// use neural::data::libsvm::{LibsvmLoader, Targets};
+///
+/// let training = LibsvmLoader::new()
+///     .features(780)
+///     .targets(Targets::MultiHot { classes: 10 })
+///     .load("train.svm")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct LibsvmLoader {
+    features: Option<usize>,
+    targets: Targets,
+}
+
+impl Default for LibsvmLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LibsvmLoader {
+    pub fn new() -> Self {
+        Self {
+            features: None,
+            targets: Targets::Value,
+        }
+    }
+
+    pub fn features(mut self, features: usize) -> Self {
+        self.features = Some(features);
+        self
+    }
+
+    pub fn targets(mut self, targets: Targets) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<LibsvmDataset, Error> {
+        self.parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn read<R: Read>(&self, mut reader: R) -> Result<LibsvmDataset, Error> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        self.parse(&text)
+    }
+
+    pub fn parse(&self, text: &str) -> Result<LibsvmDataset, Error> {
+        self.densify(&parse(text)?)
+    }
+
+    pub fn densify(&self, records: &[Record]) -> Result<LibsvmDataset, Error> {
+        let features = match self.features {
+            Some(features) => features,
+            None => records
+                .iter()
+                .filter_map(|r| r.features.last())
+                .map(|(index, _)| index + 1)
+                .max()
+                .unwrap_or(0),
+        };
+        let target_len = match self.targets {
+            Targets::Value => 1,
+            Targets::MultiHot { classes } => classes,
+        };
+        if features == 0 || target_len == 0 {
+            return Err(Error::Empty);
+        }
+
+        let mut inputs = vec![0.0; records.len() * features];
+        let mut targets = vec![0.0; records.len() * target_len];
+        for (i, record) in records.iter().enumerate() {
+            let input = &mut inputs[i * features..(i + 1) * features];
+            for (index, value) in &record.features {
+                if *index >= features {
+                    return Err(Error::IndexOutOfRange {
+                        line: record.line,
+                        index: index + 1,
+                        features,
+                    });
+                }
+                input[*index] = *value;
+            }
+
+            let target = &mut targets[i * target_len..(i + 1) * target_len];
+            match self.targets {
+                Targets::Value => {
+                    let [label] = record.labels[..] else {
+                        return Err(Error::Malformed {
+                            line: record.line,
+                            message: format!("expected one label, found {}", record.labels.len()),
+                        });
+                    };
+                    target[0] = label;
+                }
+                Targets::MultiHot { classes } => {
+                    for label in &record.labels {
+                        if label.fract() != 0.0 || *label < 0.0 || *label >= classes as f32 {
+                            return Err(Error::InvalidClass {
+                                line: record.line,
+                                label: *label,
+                                classes,
+                            });
+                        }
+                        target[*label as usize] = 1.0;
+                    }
+                }
+            }
+        }
+
+        Ok(LibsvmDataset {
+            samples: InMemoryDataset::new(inputs, targets, features, target_len),
+            qids: records.iter().map(|r| r.qid).collect(),
+        })
+    }
+}
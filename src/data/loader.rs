@@ -0,0 +1,327 @@
+use std::{
+    marker::PhantomData,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::matrix::Matrix;
+
+use super::{augment::Augmenter, Dataset};
+
+/// A batch of samples, one column per sample.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    /// `(input_len, batch_size)`
+    pub inputs: Matrix,
+    /// `(target_len, batch_size)`
+    pub targets: Matrix,
+    /// Dataset indices of the samples, in column order
+    pub indices: Vec<usize>,
+}
+
+impl Batch {
+    /// Gathers the samples at `indices` into a batch.
+    pub fn from_indices<D: Dataset + ?Sized>(dataset: &D, indices: Vec<usize>) -> Self {
+        HostBatch::gather(dataset, indices, None).upload()
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// The samples of a batch in host memory, not yet uploaded to the GPU.
+struct HostBatch {
+    inputs: Vec<f32>,
+    targets: Vec<f32>,
+    indices: Vec<usize>,
+    input_len: usize,
+    target_len: usize,
+}
+
+impl HostBatch {
+    /// Copies the samples at `indices`, augmenting the inputs as the samples
+    /// at `start..` of `epoch` if an augmenter is given.
+    fn gather<D: Dataset + ?Sized>(
+        dataset: &D,
+        indices: Vec<usize>,
+        augmentation: Option<(&Augmenter, u64, usize)>,
+    ) -> Self {
+        let input_len = dataset.input_len();
+        let target_len = dataset.target_len();
+        let mut inputs = Vec::with_capacity(indices.len() * input_len);
+        let mut targets = Vec::with_capacity(indices.len() * target_len);
+        for (i, index) in indices.iter().enumerate() {
+            inputs.extend_from_slice(dataset.input(*index));
+            if let Some((augmenter, epoch, start)) = augmentation {
+                augmenter.apply_at(&mut inputs[i * input_len..], epoch, start + i);
+            }
+            targets.extend_from_slice(dataset.target(*index));
+        }
+
+        Self {
+            inputs,
+            targets,
+            indices,
+            input_len,
+            target_len,
+        }
+    }
+
+    fn upload(self) -> Batch {
+        let n = self.indices.len();
+        Batch {
+            inputs: Matrix::from_slice_cm(&self.inputs, self.input_len, n),
+            targets: Matrix::from_slice_cm(&self.targets, self.target_len, n),
+            indices: self.indices,
+        }
+    }
+}
+
+/// Iterates over a dataset in batches, one epoch at a time.
+///
+/// With shuffling enabled the order of an epoch only depends on the seed and
+/// the epoch number, so a run resumed at a given epoch sees the same batches.
+#[derive(Debug, Clone)]
+pub struct DataLoader<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    batch_size: usize,
+    seed: Option<u64>,
+    drop_last: bool,
+    epoch: u64,
+    augmenter: Option<&'a Augmenter>,
+}
+
+impl<'a, D: Dataset + ?Sized> DataLoader<'a, D> {
+    pub fn new(dataset: &'a D, batch_size: usize) -> Self {
+        assert!(batch_size > 0);
+
+        Self {
+            dataset,
+            batch_size,
+            seed: None,
+            drop_last: false,
+            epoch: 0,
+            augmenter: None,
+        }
+    }
+
+    /// Shuffles the samples of every epoch using `seed`.
+    pub fn shuffle(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Skips the last batch of an epoch if it is smaller than the batch size.
+    pub fn drop_last(mut self, drop_last: bool) -> Self {
+        self.drop_last = drop_last;
+        self
+    }
+
+    /// Augments the inputs of every batch, see [`super::augment`].
+    pub fn augment(mut self, augmenter: &'a Augmenter) -> Self {
+        assert_eq!(self.dataset.input_len(), augmenter.shape().len());
+        self.augmenter = Some(augmenter);
+        self
+    }
+
+    pub fn dataset(&self) -> &'a D {
+        self.dataset
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// The epoch returned by the next call to [`Self::next_epoch`].
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn set_epoch(&mut self, epoch: u64) {
+        self.epoch = epoch;
+    }
+
+    /// Number of batches per epoch.
+    pub fn len(&self) -> usize {
+        if self.drop_last {
+            self.dataset.len() / self.batch_size
+        } else {
+            self.dataset.len().div_ceil(self.batch_size)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sample order of `epoch`.
+    pub fn order(&self, epoch: u64) -> Vec<usize> {
+        let mut order = (0..self.dataset.len()).collect::<Vec<usize>>();
+        if let Some(seed) = self.seed {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(epoch);
+            order.shuffle(&mut rng);
+        }
+        if self.drop_last {
+            order.truncate(self.len() * self.batch_size);
+        }
+        order
+    }
+
+    /// Batches of the current epoch, advancing to the next one.
+    pub fn next_epoch(&mut self) -> Batches<'a, D> {
+        let order = self.order(self.epoch);
+        self.epoch += 1;
+
+        Batches {
+            dataset: self.dataset,
+            batch_size: self.batch_size,
+            order,
+            pos: 0,
+            augmenter: self.augmenter,
+            epoch: self.epoch - 1,
+        }
+    }
+
+    /// Like [`Self::next_epoch`], but gathers and augments the batches on
+    /// `workers` background threads while `f` consumes them.
+    ///
+    /// Every worker keeps up to `depth` batches ready. The batches arrive in
+    /// the same order and with the same contents as from
+    /// [`Self::next_epoch`]; only the upload to the GPU happens on the
+    /// calling thread. The workers stop once `f` returns.
+    ///
+    /// ```no_run
+    /// # use neural::{data::{DataLoader, InMemoryDataset}, nn::NeuralNetwork};
+    /// # let dataset = InMemoryDataset::from_samples(&[(vec![0.0], vec![1.0])]);
+    /// # let mut nn = NeuralNetwork::new(1, vec![2], 1);
+    /// let mut loader = DataLoader::new(&dataset, 64).shuffle(42);
+    /// for _ in 0..10 {
+    ///     loader.prefetch_epoch(4, 2, |batches| {
+    ///         for batch in batches {
+    ///             nn.train_batch(&batch.inputs, &batch.targets);
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    pub fn prefetch_epoch<R, F>(&mut self, workers: usize, depth: usize, f: F) -> R
+    where
+        D: Sync,
+        F: for<'p> FnOnce(Prefetched<'p>) -> R,
+    {
+        assert!(workers > 0 && depth > 0);
+
+        let epoch = self.epoch;
+        let order = self.order(epoch);
+        self.epoch += 1;
+
+        let (dataset, augmenter, batch_size) = (self.dataset, self.augmenter, self.batch_size);
+        let batches = order.len().div_ceil(batch_size);
+        let workers = workers.min(batches);
+        let order = &order;
+        thread::scope(|scope| {
+            let mut receivers = Vec::with_capacity(workers);
+            for worker in 0..workers {
+                let (sender, receiver) = mpsc::sync_channel(depth);
+                receivers.push(receiver);
+                // Worker `w` prepares batches `w`, `w + workers`, ...
+                scope.spawn(move || {
+                    for batch in (worker..batches).step_by(workers) {
+                        let start = batch * batch_size;
+                        let end = (start + batch_size).min(order.len());
+                        let indices = order[start..end].to_vec();
+                        let augmentation = augmenter.map(|a| (a, epoch, start));
+                        let host = HostBatch::gather(dataset, indices, augmentation);
+                        if sender.send(host).is_err() {
+                            // The consumer stopped early
+                            break;
+                        }
+                    }
+                });
+            }
+
+            f(Prefetched {
+                receivers,
+                next: 0,
+                batches,
+                _scope: PhantomData,
+            })
+        })
+    }
+}
+
+/// Batches of a single epoch, see [`DataLoader::next_epoch`].
+#[derive(Debug, Clone)]
+pub struct Batches<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    batch_size: usize,
+    order: Vec<usize>,
+    pos: usize,
+    augmenter: Option<&'a Augmenter>,
+    epoch: u64,
+}
+
+impl<D: Dataset + ?Sized> Iterator for Batches<'_, D> {
+    type Item = Batch;
+
+    fn next(&mut self) -> Option<Batch> {
+        if self.pos >= self.order.len() {
+            return None;
+        }
+
+        let start = self.pos;
+        let end = (start + self.batch_size).min(self.order.len());
+        let indices = self.order[start..end].to_vec();
+        self.pos = end;
+
+        let augmentation = self.augmenter.map(|a| (a, self.epoch, start));
+        Some(HostBatch::gather(self.dataset, indices, augmentation).upload())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.order.len() - self.pos).div_ceil(self.batch_size);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<D: Dataset + ?Sized> ExactSizeIterator for Batches<'_, D> {}
+
+/// Batches of a single epoch prepared in the background, see
+/// [`DataLoader::prefetch_epoch`].
+#[derive(Debug)]
+pub struct Prefetched<'p> {
+    receivers: Vec<Receiver<HostBatch>>,
+    next: usize,
+    batches: usize,
+    _scope: PhantomData<&'p ()>,
+}
+
+impl Iterator for Prefetched<'_> {
+    type Item = Batch;
+
+    fn next(&mut self) -> Option<Batch> {
+        if self.next >= self.batches {
+            return None;
+        }
+
+        let receiver = &self.receivers[self.next % self.receivers.len()];
+        self.next += 1;
+        // Only fails if the worker panicked, which the scope reports
+        receiver.recv().ok().map(HostBatch::upload)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.batches - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Prefetched<'_> {}
@@ -0,0 +1,296 @@
+//! Loaders for MNIST and datasets sharing its file layout: Fashion-MNIST,
+//! KMNIST and EMNIST.
+//!
+//! Files are looked up in a local directory under their original names, as
+//! fetched by `data/mnist/get_dataset.sh`, with or without a `.gz` suffix.
+//! Compressed files need the `gzip` feature.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    idx::{self, Data},
+    InMemoryDataset,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Idx(idx::Error),
+    /// Neither the file nor its `.gz` counterpart exists.
+    MissingFile(PathBuf),
+    /// The files do not hold images and labels of the expected layout.
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Idx(e) => write!(f, "{e}"),
+            Error::MissingFile(path) => write!(f, "missing file {}", path.display()),
+            Error::Invalid(msg) => write!(f, "invalid dataset: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Idx(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<idx::Error> for Error {
+    fn from(e: idx::Error) -> Self {
+        Error::Idx(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    Train,
+    Test,
+}
+
+/// The EMNIST splits, see <https://www.nist.gov/itl/products-and-services/emnist-dataset>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmnistSplit {
+    ByClass,
+    ByMerge,
+    Balanced,
+    Letters,
+    Digits,
+    Mnist,
+}
+
+impl EmnistSplit {
+    fn name(&self) -> &'static str {
+        match self {
+            EmnistSplit::ByClass => "byclass",
+            EmnistSplit::ByMerge => "bymerge",
+            EmnistSplit::Balanced => "balanced",
+            EmnistSplit::Letters => "letters",
+            EmnistSplit::Digits => "digits",
+            EmnistSplit::Mnist => "mnist",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Mnist,
+    FashionMnist,
+    Kmnist,
+    Emnist(EmnistSplit),
+}
+
+impl Variant {
+    pub fn classes(&self) -> usize {
+        match self {
+            Variant::Emnist(EmnistSplit::ByClass) => 62,
+            Variant::Emnist(EmnistSplit::ByMerge) | Variant::Emnist(EmnistSplit::Balanced) => 47,
+            Variant::Emnist(EmnistSplit::Letters) => 26,
+            _ => 10,
+        }
+    }
+
+    /// Names of the image and label files.
+    fn file_names(&self, split: Split) -> (String, String) {
+        let prefix = match (self, split) {
+            (Variant::Emnist(emnist), Split::Train) => format!("emnist-{}-train", emnist.name()),
+            (Variant::Emnist(emnist), Split::Test) => format!("emnist-{}-test", emnist.name()),
+            (_, Split::Train) => "train".to_string(),
+            (_, Split::Test) => "t10k".to_string(),
+        };
+        (
+            format!("{prefix}-images-idx3-ubyte"),
+            format!("{prefix}-labels-idx1-ubyte"),
+        )
+    }
+}
+
+/// How pixel values are mapped to network inputs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Raw pixel values in `0..=255`
+    None,
+    /// Pixel values divided by 255 into `0..=1`
+    UnitRange,
+    /// `(pixel / 255 - mean) / std`
+    Standardize { mean: f32, std: f32 },
+}
+
+impl Normalization {
+    /// Mean and standard deviation of the MNIST training images.
+    pub const MNIST: Normalization = Normalization::Standardize {
+        mean: 0.1307,
+        std: 0.3081,
+    };
+
+    fn apply(&self, pixel: u8) -> f32 {
+        match self {
+            Normalization::None => pixel as f32,
+            Normalization::UnitRange => pixel as f32 / 255.0,
+            Normalization::Standardize { mean, std } => (pixel as f32 / 255.0 - mean) / std,
+        }
+    }
+}
+
+/// Images with one-hot labels.
+#[derive(Debug, Clone)]
+pub struct MnistDataset {
+    samples: InMemoryDataset,
+    labels: Vec<usize>,
+    rows: usize,
+    columns: usize,
+}
+
+impl MnistDataset {
+    /// Class index of every image.
+    pub fn labels(&self) -> &[usize] {
+        &self.labels
+    }
+
+    pub fn label(&self, index: usize) -> usize {
+        self.labels[index]
+    }
+
+    /// `(rows, columns)` of every image.
+    pub fn image_size(&self) -> (usize, usize) {
+        (self.rows, self.columns)
+    }
+}
+
+delegate_dataset!(MnistDataset);
+
+/// Loads MNIST-family datasets from a directory.
+///
+/// ```no_run
+/// use neural::data::mnist::{MnistLoader, Split, Variant};
+///
+/// let training = MnistLoader::new("./data/fashion")
+///     .variant(Variant::FashionMnist)
+///     .load(Split::Train)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct MnistLoader {
+    dir: PathBuf,
+    variant: Variant,
+    normalization: Normalization,
+}
+
+impl MnistLoader {
+    /// Loads MNIST with pixels scaled into `0..=1` unless configured otherwise.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            variant: Variant::Mnist,
+            normalization: Normalization::UnitRange,
+        }
+    }
+
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    fn find(&self, name: &str) -> Result<PathBuf, Error> {
+        let path = self.dir.join(name);
+        if path.exists() {
+            return Ok(path);
+        }
+        let gz = self.dir.join(format!("{name}.gz"));
+        if gz.exists() {
+            return Ok(gz);
+        }
+        Err(Error::MissingFile(path))
+    }
+
+    pub fn load(&self, split: Split) -> Result<MnistDataset, Error> {
+        let (images_name, labels_name) = self.variant.file_names(split);
+        let images = idx::read_file(self.find(&images_name)?)?;
+        let labels = idx::read_file(self.find(&labels_name)?)?;
+
+        let (Data::U8(pixels), Data::U8(raw_labels)) = (&images.data, &labels.data) else {
+            return Err(Error::Invalid(
+                "images and labels have to be unsigned bytes".to_string(),
+            ));
+        };
+        let (rows, columns) = match images.dims[..] {
+            [_, rows, columns] => (rows, columns),
+            _ => {
+                return Err(Error::Invalid(format!(
+                    "images have dimensions {:?}, expected [count, rows, columns]",
+                    images.dims
+                )))
+            }
+        };
+        if rows == 0 || columns == 0 {
+            return Err(Error::Invalid(format!("images of size {rows}x{columns}")));
+        }
+        if labels.dims.len() != 1 || labels.items() != images.items() {
+            return Err(Error::Invalid(format!(
+                "{} images but labels have dimensions {:?}",
+                images.items(),
+                labels.dims
+            )));
+        }
+
+        let classes = self.variant.classes();
+        // EMNIST letters are labelled 1 to 26
+        let first_label = match self.variant {
+            Variant::Emnist(EmnistSplit::Letters) => 1,
+            _ => 0,
+        };
+        let labels = raw_labels
+            .iter()
+            .map(|label| {
+                (*label as usize)
+                    .checked_sub(first_label)
+                    .filter(|label| *label < classes)
+                    .ok_or_else(|| {
+                        Error::Invalid(format!(
+                            "label {label} is out of range for {classes} classes"
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<usize>, Error>>()?;
+
+        let pixel_count = rows * columns;
+        let mut inputs = Vec::with_capacity(pixels.len());
+        for image in pixels.chunks_exact(pixel_count) {
+            match self.variant {
+                // EMNIST images are stored transposed
+                Variant::Emnist(_) => {
+                    for row in 0..rows {
+                        for column in 0..columns {
+                            inputs.push(self.normalization.apply(image[column * rows + row]));
+                        }
+                    }
+                }
+                _ => inputs.extend(image.iter().map(|p| self.normalization.apply(*p))),
+            }
+        }
+
+        let mut targets = vec![0.0; labels.len() * classes];
+        for (index, label) in labels.iter().enumerate() {
+            targets[index * classes + label] = 1.0;
+        }
+
+        Ok(MnistDataset {
+            samples: InMemoryDataset::new(inputs, targets, pixel_count, classes),
+            labels,
+            rows,
+            columns,
+        })
+    }
+}
@@ -0,0 +1,170 @@
+//! Datasets and batched iteration over them.
+
+/// Implements [`Dataset`] for a loaded dataset by delegating to the
+/// [`InMemoryDataset`] in its `samples` field.
+macro_rules! delegate_dataset {
+    ($type:ty) => {
+        impl $crate::data::Dataset for $type {
+            fn len(&self) -> usize {
+                $crate::data::Dataset::len(&self.samples)
+            }
+
+            fn input_len(&self) -> usize {
+                $crate::data::Dataset::input_len(&self.samples)
+            }
+
+            fn target_len(&self) -> usize {
+                $crate::data::Dataset::target_len(&self.samples)
+            }
+
+            fn input(&self, index: usize) -> &[f32] {
+                $crate::data::Dataset::input(&self.samples, index)
+            }
+
+            fn target(&self, index: usize) -> &[f32] {
+                $crate::data::Dataset::target(&self.samples, index)
+            }
+        }
+    };
+}
+
+pub mod augment;
+pub mod cifar;
+pub mod csv;
+pub mod idx;
+pub mod libsvm;
+mod loader;
+pub mod mnist;
+pub mod split;
+pub mod synthetic;
+
+pub use loader::{Batch, Batches, DataLoader, Prefetched};
+
+/// A fixed number of samples, each made of an input and a target vector.
+pub trait Dataset {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Length of every input vector
+    fn input_len(&self) -> usize;
+
+    /// Length of every target vector
+    fn target_len(&self) -> usize;
+
+    fn input(&self, index: usize) -> &[f32];
+
+    fn target(&self, index: usize) -> &[f32];
+}
+
+impl<D: Dataset + ?Sized> Dataset for &D {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn input_len(&self) -> usize {
+        (**self).input_len()
+    }
+
+    fn target_len(&self) -> usize {
+        (**self).target_len()
+    }
+
+    fn input(&self, index: usize) -> &[f32] {
+        (**self).input(index)
+    }
+
+    fn target(&self, index: usize) -> &[f32] {
+        (**self).target(index)
+    }
+}
+
+/// Dataset keeping all inputs and targets in two flat buffers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InMemoryDataset {
+    inputs: Vec<f32>,
+    targets: Vec<f32>,
+    input_len: usize,
+    target_len: usize,
+}
+
+impl InMemoryDataset {
+    /// `inputs` and `targets` hold the samples back to back.
+    pub fn new(inputs: Vec<f32>, targets: Vec<f32>, input_len: usize, target_len: usize) -> Self {
+        assert!(input_len > 0 && target_len > 0);
+        assert_eq!(inputs.len() % input_len, 0);
+        assert_eq!(
+            inputs.len() / input_len * target_len,
+            targets.len(),
+            "inputs and targets hold a different number of samples"
+        );
+
+        Self {
+            inputs,
+            targets,
+            input_len,
+            target_len,
+        }
+    }
+
+    pub fn from_samples(samples: &[(Vec<f32>, Vec<f32>)]) -> Self {
+        assert!(!samples.is_empty());
+
+        let input_len = samples[0].0.len();
+        let target_len = samples[0].1.len();
+        let mut inputs = Vec::with_capacity(samples.len() * input_len);
+        let mut targets = Vec::with_capacity(samples.len() * target_len);
+        for (input, target) in samples {
+            assert_eq!(input.len(), input_len);
+            assert_eq!(target.len(), target_len);
+            inputs.extend_from_slice(input);
+            targets.extend_from_slice(target);
+        }
+
+        Self::new(inputs, targets, input_len, target_len)
+    }
+
+    /// Copies the samples of any dataset.
+    pub fn from_dataset<D: Dataset + ?Sized>(dataset: &D) -> Self {
+        let mut inputs = Vec::with_capacity(dataset.len() * dataset.input_len());
+        let mut targets = Vec::with_capacity(dataset.len() * dataset.target_len());
+        for index in 0..dataset.len() {
+            inputs.extend_from_slice(dataset.input(index));
+            targets.extend_from_slice(dataset.target(index));
+        }
+
+        Self::new(inputs, targets, dataset.input_len(), dataset.target_len())
+    }
+
+    pub fn inputs(&self) -> &[f32] {
+        &self.inputs
+    }
+
+    pub fn targets(&self) -> &[f32] {
+        &self.targets
+    }
+}
+
+impl Dataset for InMemoryDataset {
+    fn len(&self) -> usize {
+        self.inputs.len() / self.input_len
+    }
+
+    fn input_len(&self) -> usize {
+        self.input_len
+    }
+
+    fn target_len(&self) -> usize {
+        self.target_len
+    }
+
+    fn input(&self, index: usize) -> &[f32] {
+        &self.inputs[index * self.input_len..(index + 1) * self.input_len]
+    }
+
+    fn target(&self, index: usize) -> &[f32] {
+        &self.targets[index * self.target_len..(index + 1) * self.target_len]
+    }
+}
@@ -0,0 +1,388 @@
+//! Splitting datasets into training, validation and test sets, and k-fold
+//! cross-validation.
+//!
+//! Splits are views over the original dataset, so no samples are copied.
+//! Stratified splits treat samples with identical targets as one class,
+//! which covers one-hot and scalar class targets alike, and keep the share of
+//! every class the same in every part.
+
+use std::collections::BTreeMap;
+
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::Dataset;
+use crate::{
+    metrics::{Metric, Report},
+    nn::NeuralNetwork,
+};
+
+/// The samples of a dataset at the given indices.
+#[derive(Debug, Clone)]
+pub struct Subset<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    indices: Vec<usize>,
+}
+
+impl<'a, D: Dataset + ?Sized> Subset<'a, D> {
+    pub fn new(dataset: &'a D, indices: Vec<usize>) -> Self {
+        assert!(indices.iter().all(|i| *i < dataset.len()));
+        Self { dataset, indices }
+    }
+
+    pub fn dataset(&self) -> &'a D {
+        self.dataset
+    }
+
+    /// Indices of the samples in the original dataset.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+impl<D: Dataset + ?Sized> Dataset for Subset<'_, D> {
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn input_len(&self) -> usize {
+        self.dataset.input_len()
+    }
+
+    fn target_len(&self) -> usize {
+        self.dataset.target_len()
+    }
+
+    fn input(&self, index: usize) -> &[f32] {
+        self.dataset.input(self.indices[index])
+    }
+
+    fn target(&self, index: usize) -> &[f32] {
+        self.dataset.target(self.indices[index])
+    }
+}
+
+/// Sample counts for `fractions` of `len` samples. The last part takes what
+/// rounding leaves over.
+fn part_sizes(len: usize, fractions: &[f32]) -> Vec<usize> {
+    assert!(!fractions.is_empty());
+    assert!(fractions.iter().all(|f| *f >= 0.0));
+    let total = fractions.iter().sum::<f32>();
+    assert!(
+        (total - 1.0).abs() < 1e-4,
+        "fractions add up to {total} instead of 1"
+    );
+
+    let mut sizes = Vec::with_capacity(fractions.len());
+    let mut assigned = 0;
+    for fraction in &fractions[..fractions.len() - 1] {
+        let size = ((len as f32 * fraction).round() as usize).min(len - assigned);
+        sizes.push(size);
+        assigned += size;
+    }
+    sizes.push(len - assigned);
+    sizes
+}
+
+/// Sample indices grouped by target, in order of first appearance.
+fn classes<D: Dataset + ?Sized>(dataset: &D) -> Vec<Vec<usize>> {
+    let mut classes: BTreeMap<Vec<u32>, usize> = BTreeMap::new();
+    let mut members = Vec::new();
+    for index in 0..dataset.len() {
+        let key = dataset.target(index).iter().map(|v| v.to_bits()).collect();
+        let class = *classes.entry(key).or_insert_with(|| {
+            members.push(Vec::new());
+            members.len() - 1
+        });
+        members[class].push(index);
+    }
+    members
+}
+
+/// Shuffles the samples with `seed` and splits them into parts of the given
+/// fractions, e.g. `&[0.8, 0.1, 0.1]` for training, validation and test sets.
+pub fn random_split<'a, D: Dataset + ?Sized>(
+    dataset: &'a D,
+    fractions: &[f32],
+    seed: u64,
+) -> Vec<Subset<'a, D>> {
+    let mut order = (0..dataset.len()).collect::<Vec<usize>>();
+    order.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+
+    let mut parts = Vec::with_capacity(fractions.len());
+    let mut rest = &order[..];
+    for size in part_sizes(dataset.len(), fractions) {
+        let (part, tail) = rest.split_at(size);
+        parts.push(Subset::new(dataset, part.to_vec()));
+        rest = tail;
+    }
+    parts
+}
+
+/// Like [`random_split`], but splits every class separately so the parts
+/// keep the class proportions of the whole dataset.
+pub fn stratified_split<'a, D: Dataset + ?Sized>(
+    dataset: &'a D,
+    fractions: &[f32],
+    seed: u64,
+) -> Vec<Subset<'a, D>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut parts = vec![Vec::new(); fractions.len()];
+    for mut members in classes(dataset) {
+        members.shuffle(&mut rng);
+        let mut rest = &members[..];
+        for (part, size) in parts.iter_mut().zip(part_sizes(members.len(), fractions)) {
+            let (taken, tail) = rest.split_at(size);
+            part.extend_from_slice(taken);
+            rest = tail;
+        }
+    }
+    parts
+        .into_iter()
+        .map(|mut part| {
+            // Mix the classes again
+            part.shuffle(&mut rng);
+            Subset::new(dataset, part)
+        })
+        .collect()
+}
+
+/// Shorthand for a two-way [`random_split`].
+pub fn train_test_split<D: Dataset + ?Sized>(
+    dataset: &D,
+    test_fraction: f32,
+    seed: u64,
+) -> (Subset<'_, D>, Subset<'_, D>) {
+    let mut parts = random_split(dataset, &[1.0 - test_fraction, test_fraction], seed);
+    let test = parts.pop().unwrap();
+    (parts.pop().unwrap(), test)
+}
+
+/// Validation results of one fold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoldMetrics {
+    pub fold: usize,
+    pub train_len: usize,
+    pub validation_len: usize,
+    /// The requested metrics on the validation samples
+    pub report: Report,
+}
+
+/// Mean and standard deviation of a metric across folds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aggregate {
+    pub mean: f32,
+    pub std: f32,
+}
+
+impl Aggregate {
+    fn of(values: impl Iterator<Item = f32> + Clone) -> Self {
+        let n = values.clone().count() as f32;
+        let mean = values.clone().sum::<f32>() / n;
+        let var = values.map(|v| (v - mean) * (v - mean)).sum::<f32>() / n;
+        Self {
+            mean,
+            std: var.sqrt(),
+        }
+    }
+}
+
+/// Results of [`KFold::cross_validate`]. Metrics that weren't requested
+/// are `None` or empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidation {
+    pub folds: Vec<FoldMetrics>,
+    /// Mean squared error over all outputs, always computed
+    pub loss: Aggregate,
+    pub accuracy: Option<Aggregate>,
+    /// `(k, accuracy)` for every requested `k`
+    pub top_k_accuracy: Vec<(usize, Aggregate)>,
+    pub roc_auc: Option<Aggregate>,
+    pub average_precision: Option<Aggregate>,
+}
+
+/// Aggregates a value taken from the report of every fold, `None` if any
+/// fold lacks it.
+fn aggregate<F: Fn(&Report) -> Option<f32>>(folds: &[FoldMetrics], metric: F) -> Option<Aggregate> {
+    let values = folds
+        .iter()
+        .map(|f| metric(&f.report))
+        .collect::<Option<Vec<f32>>>()?;
+    Some(Aggregate::of(values.into_iter()))
+}
+
+impl CrossValidation {
+    fn new(folds: Vec<FoldMetrics>) -> Self {
+        let ks = folds[0].report.top_k_accuracy.iter().map(|(k, _)| *k);
+        let top_k_accuracy = ks
+            .enumerate()
+            .map(|(i, k)| {
+                let values = folds.iter().map(|f| f.report.top_k_accuracy[i].1);
+                (k, Aggregate::of(values))
+            })
+            .collect();
+        Self {
+            loss: Aggregate::of(folds.iter().map(|f| f.report.loss)),
+            accuracy: aggregate(&folds, |r| r.accuracy),
+            top_k_accuracy,
+            roc_auc: aggregate(&folds, |r| r.roc_auc),
+            average_precision: aggregate(&folds, |r| r.average_precision),
+            folds,
+        }
+    }
+
+    /// Aggregates a value taken from the report of every fold, e.g.
+    /// `|r| r.regression.as_ref().map(|r| r.average.r2)`. `None` if any fold
+    /// lacks it.
+    pub fn aggregate<F: Fn(&Report) -> Option<f32>>(&self, metric: F) -> Option<Aggregate> {
+        aggregate(&self.folds, metric)
+    }
+}
+
+/// Splits a dataset into `k` folds, each used once for validation while the
+/// others are trained on.
+///
+/// ```no_run
+/// use neural::{
+///     data::{split::KFold, DataLoader, Dataset, InMemoryDataset},
+///     metrics::Metric,
+///     nn::NeuralNetwork,
+/// };
+///
+/// # let dataset = InMemoryDataset::from_samples(&[(vec![0.0], vec![1.0])]);
+/// let results = KFold::new(5).shuffle(42).stratified(true).cross_validate(
+///     &dataset,
+///     &[Metric::Accuracy],
+///     |_fold| NeuralNetwork::new(dataset.input_len(), vec![16], dataset.target_len()),
+///     |nn, training| {
+///         let mut loader = DataLoader::new(training, 32).shuffle(7);
+///         for _ in 0..10 {
+///             for batch in loader.next_epoch() {
+///                 nn.train_batch(&batch.inputs, &batch.targets);
+///             }
+///         }
+///     },
+/// );
+/// let accuracy = results.accuracy.unwrap();
+/// println!("accuracy {:.3} ± {:.3}", accuracy.mean, accuracy.std);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KFold {
+    k: usize,
+    seed: Option<u64>,
+    stratified: bool,
+}
+
+impl KFold {
+    pub fn new(k: usize) -> Self {
+        assert!(k >= 2, "k-fold needs at least two folds");
+
+        Self {
+            k,
+            seed: None,
+            stratified: false,
+        }
+    }
+
+    /// Shuffles the samples with `seed` before assigning them to folds.
+    pub fn shuffle(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Deals the samples of every class evenly over the folds.
+    pub fn stratified(mut self, stratified: bool) -> Self {
+        self.stratified = stratified;
+        self
+    }
+
+    /// Sample indices of every fold.
+    pub fn fold_indices<D: Dataset + ?Sized>(&self, dataset: &D) -> Vec<Vec<usize>> {
+        assert!(dataset.len() >= self.k, "fewer samples than folds");
+
+        let mut rng = self.seed.map(ChaCha8Rng::seed_from_u64);
+        let mut folds = vec![Vec::new(); self.k];
+        if self.stratified {
+            // Continue dealing where the previous class stopped so the fold
+            // sizes stay balanced
+            let mut next = 0;
+            for mut members in classes(dataset) {
+                if let Some(rng) = &mut rng {
+                    members.shuffle(rng);
+                }
+                for index in members {
+                    folds[next].push(index);
+                    next = (next + 1) % self.k;
+                }
+            }
+            for fold in &mut folds {
+                fold.sort_unstable();
+            }
+        } else {
+            let mut order = (0..dataset.len()).collect::<Vec<usize>>();
+            if let Some(rng) = &mut rng {
+                order.shuffle(rng);
+            }
+            let (size, extra) = (order.len() / self.k, order.len() % self.k);
+            let mut rest = &order[..];
+            for (i, fold) in folds.iter_mut().enumerate() {
+                let (taken, tail) = rest.split_at(size + usize::from(i < extra));
+                fold.extend_from_slice(taken);
+                rest = tail;
+            }
+        }
+        folds
+    }
+
+    /// Training and validation set of every fold.
+    pub fn splits<'a, D: Dataset + ?Sized>(
+        &self,
+        dataset: &'a D,
+    ) -> Vec<(Subset<'a, D>, Subset<'a, D>)> {
+        let folds = self.fold_indices(dataset);
+        (0..self.k)
+            .map(|i| {
+                let training = folds
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .flat_map(|(_, fold)| fold.iter().copied())
+                    .collect();
+                (
+                    Subset::new(dataset, training),
+                    Subset::new(dataset, folds[i].clone()),
+                )
+            })
+            .collect()
+    }
+
+    /// Builds a fresh network with `build` for every fold, trains it with
+    /// `train` on the other folds and evaluates `metrics` on the fold.
+    pub fn cross_validate<D, B, T>(
+        &self,
+        dataset: &D,
+        metrics: &[Metric],
+        mut build: B,
+        mut train: T,
+    ) -> CrossValidation
+    where
+        D: Dataset + ?Sized,
+        B: FnMut(usize) -> NeuralNetwork,
+        T: FnMut(&mut NeuralNetwork, &Subset<'_, D>),
+    {
+        let mut folds = Vec::with_capacity(self.k);
+        for (fold, (training, validation)) in self.splits(dataset).into_iter().enumerate() {
+            let mut nn = build(fold);
+            train(&mut nn, &training);
+
+            folds.push(FoldMetrics {
+                fold,
+                train_len: training.len(),
+                validation_len: validation.len(),
+                report: nn.evaluate(&validation, metrics),
+            });
+        }
+
+        CrossValidation::new(folds)
+    }
+}
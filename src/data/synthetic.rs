@@ -0,0 +1,167 @@
+//! Seeded generators for small toy problems.
+//!
+//! The same arguments always give the same dataset, so the generators can
+//! back quick regression tests of what a network is able to learn.
+//! Classification problems have one-hot targets and an equal number of
+//! samples per class, assigned round robin. Regression problems have a
+//! single target value. `noise` is the standard deviation of the Gaussian
+//! noise added to inputs (classification) or targets (regression).
+//! Zero samples give an empty dataset.
+
+use std::f32::consts::PI;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::InMemoryDataset;
+use crate::labels::one_hot_vec;
+
+/// Standard normal sample using the Box-Muller transform.
+pub(crate) fn normal(rng: &mut ChaCha8Rng) -> f32 {
+    let u1 = 1.0 - rng.gen::<f32>();
+    let u2 = rng.gen::<f32>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// Builds a classification dataset from a function producing an input of
+/// `input_len` values for a sample of the given class.
+fn classification<F>(
+    samples: usize,
+    input_len: usize,
+    classes: usize,
+    seed: u64,
+    mut point: F,
+) -> InMemoryDataset
+where
+    F: FnMut(&mut ChaCha8Rng, usize) -> Vec<f32>,
+{
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut inputs = Vec::with_capacity(samples * input_len);
+    let mut targets = Vec::with_capacity(samples * classes);
+    for i in 0..samples {
+        let class = i % classes;
+        inputs.extend(point(&mut rng, class));
+        targets.extend(one_hot_vec(class, classes));
+    }
+    InMemoryDataset::new(inputs, targets, input_len, classes)
+}
+
+fn add_noise(rng: &mut ChaCha8Rng, point: Vec<f32>, noise: f32) -> Vec<f32> {
+    point.into_iter().map(|v| v + noise * normal(rng)).collect()
+}
+
+/// The corners of the unit square, class 1 where exactly one coordinate is
+/// one.
+pub fn xor(samples: usize, noise: f32, seed: u64) -> InMemoryDataset {
+    classification(samples, 2, 2, seed, |rng, class| {
+        let a = rng.gen_range(0..2) as f32;
+        let b = if class == 1 { 1.0 - a } else { a };
+        add_noise(rng, vec![a, b], noise)
+    })
+}
+
+/// Two interleaving half circles.
+pub fn moons(samples: usize, noise: f32, seed: u64) -> InMemoryDataset {
+    classification(samples, 2, 2, seed, |rng, class| {
+        let t = rng.gen_range(0.0..PI);
+        let point = match class {
+            0 => vec![t.cos(), t.sin()],
+            _ => vec![1.0 - t.cos(), 0.5 - t.sin()],
+        };
+        add_noise(rng, point, noise)
+    })
+}
+
+/// A unit circle around a smaller one of radius `factor`, class 1 being
+/// the inner circle.
+pub fn circles(samples: usize, noise: f32, factor: f32, seed: u64) -> InMemoryDataset {
+    assert!((0.0..1.0).contains(&factor));
+
+    classification(samples, 2, 2, seed, |rng, class| {
+        let t = rng.gen_range(0.0..2.0 * PI);
+        let r = if class == 0 { 1.0 } else { factor };
+        add_noise(rng, vec![r * t.cos(), r * t.sin()], noise)
+    })
+}
+
+/// One spiral arm per class, winding out from the origin to radius one.
+/// The noise is added to the angle.
+pub fn spirals(samples: usize, classes: usize, noise: f32, seed: u64) -> InMemoryDataset {
+    assert!(classes >= 2);
+
+    classification(samples, 2, classes, seed, |rng, class| {
+        let r = rng.gen::<f32>();
+        let angle = class as f32 * 2.0 * PI / classes as f32 + r * 4.0 + noise * normal(rng);
+        vec![r * angle.cos(), r * angle.sin()]
+    })
+}
+
+/// Gaussian clusters of standard deviation `std` around `centers`, one
+/// class per center.
+pub fn blobs(samples: usize, centers: &[Vec<f32>], std: f32, seed: u64) -> InMemoryDataset {
+    assert!(!centers.is_empty());
+    assert!(centers.iter().all(|c| c.len() == centers[0].len()));
+
+    classification(
+        samples,
+        centers[0].len(),
+        centers.len(),
+        seed,
+        |rng, class| add_noise(rng, centers[class].clone(), std),
+    )
+}
+
+/// Points in the unit square colored like a checkerboard of
+/// `tiles` x `tiles` fields.
+pub fn checkerboard(samples: usize, tiles: usize, seed: u64) -> InMemoryDataset {
+    assert!(tiles >= 2);
+
+    classification(samples, 2, 2, seed, |rng, class| loop {
+        let x = rng.gen::<f32>();
+        let y = rng.gen::<f32>();
+        let field = (x * tiles as f32) as usize + (y * tiles as f32) as usize;
+        if field % 2 == class {
+            break vec![x, y];
+        }
+    })
+}
+
+/// Builds a regression dataset of `f(x)` for `x` uniform in `range`.
+fn regression<F: Fn(f32) -> f32>(
+    samples: usize,
+    range: (f32, f32),
+    noise: f32,
+    seed: u64,
+    f: F,
+) -> InMemoryDataset {
+    assert!(range.0 < range.1);
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut inputs = Vec::with_capacity(samples);
+    let mut targets = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let x = rng.gen_range(range.0..range.1);
+        inputs.push(x);
+        targets.push(f(x) + noise * normal(&mut rng));
+    }
+    InMemoryDataset::new(inputs, targets, 1, 1)
+}
+
+/// `sin(x)` for `x` in `-π..π`.
+pub fn sine(samples: usize, noise: f32, seed: u64) -> InMemoryDataset {
+    regression(samples, (-PI, PI), noise, seed, f32::sin)
+}
+
+/// The polynomial `coefficients[0] + coefficients[1] * x + ...` for `x` in
+/// `range`.
+pub fn polynomial(
+    samples: usize,
+    coefficients: &[f32],
+    range: (f32, f32),
+    noise: f32,
+    seed: u64,
+) -> InMemoryDataset {
+    regression(samples, range, noise, seed, |x| {
+        coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+    })
+}
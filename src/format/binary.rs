@@ -0,0 +1,211 @@
+//! Versioned binary format for [`NeuralNetwork`]s.
+//!
+//! All integers and floats are little-endian.
+//!
+//! ```text
+//! magic          8 bytes  "NEURALNN"
+//! version        u32      currently 1
+//! learning_rate  f32
+//! layer_count    u32
+//! layer_count times:
+//!     rows       u32      neurons in the layer
+//!     columns    u32      inputs to the layer
+//!     activation u8       0 = sigmoid, 1 = relu, 2 = tanh, 3 = softmax,
+//!                             4 = identity
+//! layer_count times:
+//!     weights    rows * columns f32, column major
+//!     bias       rows f32
+//! checksum       u32      CRC-32 of every preceding byte
+//! ```
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    matrix::Matrix,
+    nn::{Activation, Layer, NeuralNetwork},
+};
+
+use super::{crc32, Cursor, Framing};
+
+pub const MAGIC: &[u8; 8] = b"NEURALNN";
+pub const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    /// The input ended before the announced amount of data was read.
+    Truncated,
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// Bytes follow the checksum.
+    TrailingData(usize),
+    UnknownActivation(u8),
+    /// The layer shapes do not form a valid network.
+    InvalidArchitecture(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::BadMagic => write!(f, "not a neural network file (bad magic number)"),
+            Error::UnsupportedVersion(v) => {
+                write!(f, "unsupported format version {v} (expected {VERSION})")
+            }
+            Error::Truncated => write!(f, "file is truncated"),
+            Error::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch: expected {expected:#010x}, found {found:#010x}"
+            ),
+            Error::TrailingData(n) => write!(f, "{n} unexpected bytes after the checksum"),
+            Error::UnknownActivation(a) => write!(f, "unknown activation id {a}"),
+            Error::InvalidArchitecture(msg) => write!(f, "invalid architecture: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<Framing> for Error {
+    fn from(e: Framing) -> Self {
+        match e {
+            Framing::BadMagic => Error::BadMagic,
+            Framing::UnsupportedVersion(v) => Error::UnsupportedVersion(v),
+            Framing::Truncated => Error::Truncated,
+            Framing::ChecksumMismatch { expected, found } => {
+                Error::ChecksumMismatch { expected, found }
+            }
+            Framing::TrailingData(n) => Error::TrailingData(n),
+        }
+    }
+}
+
+fn activation_id(activation: Activation) -> u8 {
+    match activation {
+        Activation::Sigmoid => 0,
+        Activation::Relu => 1,
+        Activation::Tanh => 2,
+        Activation::Softmax => 3,
+        Activation::Identity => 4,
+    }
+}
+
+fn activation_from_id(id: u8) -> Result<Activation, Error> {
+    match id {
+        0 => Ok(Activation::Sigmoid),
+        1 => Ok(Activation::Relu),
+        2 => Ok(Activation::Tanh),
+        3 => Ok(Activation::Softmax),
+        4 => Ok(Activation::Identity),
+        _ => Err(Error::UnknownActivation(id)),
+    }
+}
+
+pub fn write<W: Write>(nn: &NeuralNetwork, writer: &mut W) -> Result<(), Error> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&nn.learning_rate().to_le_bytes());
+    buf.extend_from_slice(&(nn.layers().len() as u32).to_le_bytes());
+
+    for layer in nn.layers() {
+        let (rows, columns) = layer.weights.size();
+        buf.extend_from_slice(&(rows as u32).to_le_bytes());
+        buf.extend_from_slice(&(columns as u32).to_le_bytes());
+        buf.push(activation_id(layer.activation));
+    }
+
+    for layer in nn.layers() {
+        for v in layer.weights.to_vec() {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in layer.bias.to_vec() {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    let checksum = crc32(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+
+    writer.write_all(&buf)?;
+    Ok(())
+}
+
+pub fn read<R: Read>(reader: &mut R) -> Result<NeuralNetwork, Error> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+
+    let mut cursor = Cursor::open(&buf, MAGIC, VERSION)?;
+    let learning_rate = cursor.f32()?;
+    let layer_count = cursor.u32()? as usize;
+    if layer_count == 0 {
+        return Err(Error::InvalidArchitecture("no layers".to_string()));
+    }
+
+    let mut shapes = Vec::new();
+    for _ in 0..layer_count {
+        let rows = cursor.u32()? as usize;
+        let columns = cursor.u32()? as usize;
+        let activation = activation_from_id(cursor.u8()?)?;
+        if rows == 0 || columns == 0 {
+            return Err(Error::InvalidArchitecture(format!(
+                "layer {} has shape {rows}x{columns}",
+                shapes.len()
+            )));
+        }
+        if let Some((prev_rows, _, _)) = shapes.last() {
+            if *prev_rows != columns {
+                return Err(Error::InvalidArchitecture(format!(
+                    "layer {} takes {columns} inputs but the previous layer has {prev_rows} outputs",
+                    shapes.len()
+                )));
+            }
+        }
+        shapes.push((rows, columns, activation));
+    }
+
+    let mut data = Vec::new();
+    for (rows, columns, _) in &shapes {
+        let weights = cursor.f32s(rows * columns)?;
+        let bias = cursor.f32s(*rows)?;
+        data.push((weights, bias));
+    }
+
+    cursor.finish()?;
+
+    let layers = shapes
+        .into_iter()
+        .zip(data)
+        .map(|((rows, columns, activation), (weights, bias))| {
+            Layer::new(
+                Matrix::from_slice_cm(&weights, rows, columns),
+                Matrix::from_slice_cm(&bias, rows, 1),
+                activation,
+            )
+        })
+        .collect();
+
+    let mut nn = NeuralNetwork::from_layers(layers);
+    nn.set_learning_rate(learning_rate);
+    Ok(nn)
+}
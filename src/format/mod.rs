@@ -0,0 +1,109 @@
+//! On-disk representations of networks and matrices.
+
+pub mod binary;
+pub mod npy;
+pub mod onnx;
+mod protobuf;
+#[cfg(feature = "safetensors")]
+pub mod safetensors;
+#[cfg(feature = "serde")]
+mod serde_support;
+
+/// CRC-32 (IEEE 802.3) as used by zip, gzip and PNG.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Why a file read with [`Cursor`] is invalid. Every format converts this
+/// into variants of its own error type.
+#[derive(Debug)]
+pub(crate) enum Framing {
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    ChecksumMismatch { expected: u32, found: u32 },
+    TrailingData(usize),
+}
+
+/// Reads the little-endian files of [`binary`], [`crate::checkpoint`] and
+/// [`crate::preprocessing`]: a magic number, a `u32` version, the body and
+/// a CRC-32 of every preceding byte.
+pub(crate) struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    /// Checks the magic number and version, leaving the cursor at the body.
+    pub(crate) fn open(buf: &'a [u8], magic: &[u8], version: u32) -> Result<Self, Framing> {
+        if buf.len() < magic.len() || &buf[..magic.len()] != magic {
+            return Err(Framing::BadMagic);
+        }
+        let mut cursor = Self {
+            buf,
+            pos: magic.len(),
+        };
+        let found = cursor.u32()?;
+        if found != version {
+            return Err(Framing::UnsupportedVersion(found));
+        }
+        Ok(cursor)
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], Framing> {
+        let end = self.pos.checked_add(n).ok_or(Framing::Truncated)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(Framing::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], Framing> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Framing> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Framing> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Framing> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, Framing> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn f32s(&mut self, n: usize) -> Result<Vec<f32>, Framing> {
+        let bytes = self.take(n.checked_mul(4).ok_or(Framing::Truncated)?)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect())
+    }
+
+    /// Reads the checksum, which must end the input, and verifies it.
+    pub(crate) fn finish(mut self) -> Result<(), Framing> {
+        let body_len = self.pos;
+        let expected = self.u32()?;
+        if self.pos != self.buf.len() {
+            return Err(Framing::TrailingData(self.buf.len() - self.pos));
+        }
+        let found = crc32(&self.buf[..body_len]);
+        if expected != found {
+            return Err(Framing::ChecksumMismatch { expected, found });
+        }
+        Ok(())
+    }
+}
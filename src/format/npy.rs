@@ -0,0 +1,284 @@
+//! NumPy `.npy` and `.npz` files.
+//!
+//! `<f4` and `<f8` arrays in C or Fortran order with one or two dimensions
+//! can be read; one dimensional arrays become column vectors. Matrices are
+//! written as two dimensional `<f4` arrays, or `<f8` with [`write_as`], in
+//! Fortran order, which is our column-major storage as is. `.npz` archives
+//! need the `npz` feature.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+use crate::matrix::Matrix;
+
+pub const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// Element type of a written array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtype {
+    /// `<f4`, the precision of [`Matrix`]
+    F32,
+    /// `<f8`
+    F64,
+}
+
+impl Dtype {
+    fn descr(&self) -> &'static str {
+        match self {
+            Dtype::F32 => "<f4",
+            Dtype::F64 => "<f8",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u8, u8),
+    InvalidHeader(String),
+    UnsupportedDtype(String),
+    UnsupportedShape(Vec<usize>),
+    /// The input ended before the announced amount of data was read.
+    Truncated,
+    #[cfg(feature = "npz")]
+    Zip(zip::result::ZipError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::BadMagic => write!(f, "not a .npy file (bad magic number)"),
+            Error::UnsupportedVersion(major, minor) => {
+                write!(f, "unsupported .npy version {major}.{minor}")
+            }
+            Error::InvalidHeader(msg) => write!(f, "invalid header: {msg}"),
+            Error::UnsupportedDtype(descr) => {
+                write!(f, "unsupported dtype `{descr}` (expected `<f4` or `<f8`)")
+            }
+            Error::UnsupportedShape(shape) => {
+                write!(
+                    f,
+                    "unsupported shape {shape:?} (expected 1 or 2 non-zero dimensions)"
+                )
+            }
+            Error::Truncated => write!(f, "file is truncated"),
+            #[cfg(feature = "npz")]
+            Error::Zip(e) => write!(f, "zip error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            #[cfg(feature = "npz")]
+            Error::Zip(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::Truncated,
+            _ => Error::Io(e),
+        }
+    }
+}
+
+#[cfg(feature = "npz")]
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::Zip(e)
+    }
+}
+
+/// Returns the raw value stored under `key` in the header dictionary.
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, Error> {
+    let missing = || Error::InvalidHeader(format!("missing key `{key}`"));
+    let start = header.find(&format!("'{key}'")).ok_or_else(missing)? + key.len() + 2;
+    let rest = header[start..].trim_start();
+    let rest = rest.strip_prefix(':').ok_or_else(missing)?.trim_start();
+
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|i| i + 1)
+    } else if let Some(quoted) = rest.strip_prefix('\'') {
+        quoted.find('\'').map(|i| i + 2)
+    } else {
+        rest.find([',', '}'])
+    };
+    let end = end.ok_or_else(|| Error::InvalidHeader(format!("unterminated value for `{key}`")))?;
+    Ok(rest[..end].trim())
+}
+
+pub fn read<R: Read>(reader: &mut R) -> Result<Matrix, Error> {
+    let mut magic = [0u8; 6];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(Error::BadMagic);
+    }
+
+    let mut version = [0u8; 2];
+    reader.read_exact(&mut version)?;
+    let header_len = match version {
+        [1, 0] => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        [2, 0] | [3, 0] => {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        [major, minor] => return Err(Error::UnsupportedVersion(major, minor)),
+    };
+
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8(header)
+        .map_err(|_| Error::InvalidHeader("header is not valid text".to_string()))?;
+
+    let descr = header_value(&header, "descr")?.trim_matches('\'');
+    let item_size = match descr {
+        "<f4" => 4,
+        "<f8" => 8,
+        _ => return Err(Error::UnsupportedDtype(descr.to_string())),
+    };
+
+    let fortran_order = match header_value(&header, "fortran_order")? {
+        "True" => true,
+        "False" => false,
+        v => {
+            return Err(Error::InvalidHeader(format!(
+                "fortran_order is `{v}`, expected True or False"
+            )))
+        }
+    };
+
+    let shape = header_value(&header, "shape")?;
+    let shape = shape
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| {
+            d.parse::<usize>()
+                .map_err(|_| Error::InvalidHeader(format!("invalid dimension `{d}` in shape")))
+        })
+        .collect::<Result<Vec<usize>, Error>>()?;
+    let (rows, columns) = match shape[..] {
+        [rows] => (rows, 1),
+        [rows, columns] => (rows, columns),
+        _ => return Err(Error::UnsupportedShape(shape)),
+    };
+    // Matrices can't be empty
+    if rows == 0 || columns == 0 {
+        return Err(Error::UnsupportedShape(shape));
+    }
+
+    let len = rows
+        .checked_mul(columns)
+        .and_then(|n| n.checked_mul(item_size))
+        .ok_or_else(|| Error::InvalidHeader(format!("shape {shape:?} is too large")))?;
+    // Read instead of allocating the announced size up front, so a crafted
+    // header can't request more memory than the input actually holds
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(Error::Truncated);
+    }
+    let data = if item_size == 4 {
+        bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<f32>>()
+    } else {
+        bytes
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
+            .collect::<Vec<f32>>()
+    };
+
+    if fortran_order {
+        Ok(Matrix::from_slice_cm(&data, rows, columns))
+    } else {
+        Ok(Matrix::from_slice_rm(&data, rows, columns))
+    }
+}
+
+/// Writes `m` as a `<f4` array.
+pub fn write<W: Write>(m: &Matrix, writer: &mut W) -> Result<(), Error> {
+    write_as(m, writer, Dtype::F32)
+}
+
+/// Writes `m` as an array of `dtype`.
+pub fn write_as<W: Write>(m: &Matrix, writer: &mut W, dtype: Dtype) -> Result<(), Error> {
+    let (rows, columns) = m.size();
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': True, 'shape': ({rows}, {columns}), }}",
+        dtype.descr()
+    );
+    // Magic, version and header length take up 10 bytes, the data has to
+    // start at a multiple of 64 and the header ends with a newline
+    let unpadded = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    let values = m.to_vec();
+    let data = match dtype {
+        Dtype::F32 => values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<u8>>(),
+        Dtype::F64 => values
+            .iter()
+            .flat_map(|v| (*v as f64).to_le_bytes())
+            .collect::<Vec<u8>>(),
+    };
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+/// Reads every array in a `.npz` archive, in archive order. The `.npy`
+/// extension is stripped from the names.
+#[cfg(feature = "npz")]
+pub fn read_npz<R: Read + io::Seek>(reader: R) -> Result<Vec<(String, Matrix)>, Error> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let mut matrices = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let name = file.name().trim_end_matches(".npy").to_string();
+        matrices.push((name, read(&mut file)?));
+    }
+    Ok(matrices)
+}
+
+/// Writes the named matrices into an uncompressed `.npz` archive, as
+/// `numpy.savez` does.
+#[cfg(feature = "npz")]
+pub fn write_npz<W: Write + io::Seek>(
+    matrices: &[(&str, &Matrix)],
+    writer: W,
+) -> Result<(), Error> {
+    let mut archive = zip::ZipWriter::new(writer);
+    let options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, m) in matrices {
+        archive.start_file(format!("{name}.npy"), options)?;
+        write(m, &mut archive)?;
+    }
+    archive.finish()?;
+    Ok(())
+}
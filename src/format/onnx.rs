@@ -0,0 +1,756 @@
+//! ONNX export and import.
+//!
+//! A network becomes a graph with one float input `input` of shape
+//! `[N, n_input]` and one output `output` of shape `[N, n_output]`. Every
+//! layer is a `Gemm` node with `transB = 1` against the initializers
+//! `layers.{i}.weight` (`[outputs, inputs]`) and `layers.{i}.bias`
+//! (`[outputs]`), followed by a node for its activation unless the layer is
+//! linear.
+//!
+//! Importing accepts any graph that is a single chain of dense layers, each
+//! being a `Gemm` or a `MatMul` optionally followed by an `Add` of a bias,
+//! optionally followed by one of `Relu`, `Sigmoid`, `Tanh` or `Softmax`.
+//! Weights and biases have to be float or double initializers.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    matrix::Matrix,
+    nn::{Activation, Layer, NeuralNetwork},
+};
+
+use super::protobuf::{Decoder, Encoder, Value};
+
+pub const IR_VERSION: i64 = 8;
+pub const OPSET_VERSION: i64 = 13;
+
+/// Operators understood by [`read`].
+pub const SUPPORTED_OPERATORS: &[&str] = &[
+    "Gemm", "MatMul", "Add", "Relu", "Sigmoid", "Tanh", "Softmax",
+];
+
+// TensorProto.DataType
+const FLOAT: i64 = 1;
+const DOUBLE: i64 = 11;
+// AttributeProto.AttributeType.INT
+const ATTRIBUTE_INT: i64 = 2;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The protobuf encoding is malformed.
+    Decode(String),
+    /// The graph contains operators outside of [`SUPPORTED_OPERATORS`].
+    UnsupportedOperators(Vec<String>),
+    UnsupportedTensor(String),
+    /// The graph is not a chain of dense layers.
+    InvalidGraph(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Decode(msg) => write!(f, "malformed ONNX model: {msg}"),
+            Error::UnsupportedOperators(ops) => write!(
+                f,
+                "unsupported operators: {} (supported are {})",
+                ops.join(", "),
+                SUPPORTED_OPERATORS.join(", ")
+            ),
+            Error::UnsupportedTensor(msg) => write!(f, "unsupported tensor: {msg}"),
+            Error::InvalidGraph(msg) => write!(f, "graph is not a dense network: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Error::Decode(msg)
+    }
+}
+
+fn value_info(e: &mut Encoder, field: u32, name: &str, features: usize) {
+    e.message(field, |info| {
+        info.string(1, name);
+        info.message(2, |ty| {
+            ty.message(1, |tensor| {
+                tensor.int64(1, FLOAT);
+                tensor.message(2, |shape| {
+                    shape.message(1, |dim| dim.string(2, "N"));
+                    shape.message(1, |dim| dim.int64(1, features as i64));
+                });
+            });
+        });
+    });
+}
+
+fn initializer(e: &mut Encoder, name: &str, dims: &[usize], data: &[f32]) {
+    e.message(5, |tensor| {
+        for dim in dims {
+            tensor.int64(1, *dim as i64);
+        }
+        tensor.int64(2, FLOAT);
+        tensor.string(8, name);
+        let raw = data
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<u8>>();
+        tensor.bytes(9, &raw);
+    });
+}
+
+fn node(
+    e: &mut Encoder,
+    op_type: &str,
+    name: &str,
+    inputs: &[&str],
+    output: &str,
+    attributes: &[(&str, i64)],
+) {
+    e.message(1, |node| {
+        for input in inputs {
+            node.string(1, input);
+        }
+        node.string(2, output);
+        node.string(3, name);
+        node.string(4, op_type);
+        for (key, value) in attributes {
+            node.message(5, |attribute| {
+                attribute.string(1, key);
+                attribute.int64(3, *value);
+                attribute.int64(20, ATTRIBUTE_INT);
+            });
+        }
+    });
+}
+
+fn activation_op(activation: Activation) -> Option<&'static str> {
+    match activation {
+        Activation::Sigmoid => Some("Sigmoid"),
+        Activation::Relu => Some("Relu"),
+        Activation::Tanh => Some("Tanh"),
+        Activation::Softmax => Some("Softmax"),
+        Activation::Identity => None,
+    }
+}
+
+pub fn to_bytes(nn: &NeuralNetwork) -> Vec<u8> {
+    let mut model = Encoder::default();
+    model.int64(1, IR_VERSION);
+    model.string(2, "neural");
+    model.string(3, env!("CARGO_PKG_VERSION"));
+
+    model.message(7, |graph| {
+        let layer_count = nn.layers().len();
+        let mut input = "input".to_string();
+        for (index, layer) in nn.layers().iter().enumerate() {
+            let weight = format!("layers.{index}.weight");
+            let bias = format!("layers.{index}.bias");
+            let gemm = format!("layers.{index}.gemm");
+            let output = if index + 1 == layer_count {
+                "output".to_string()
+            } else {
+                format!("layers.{index}.output")
+            };
+
+            match activation_op(layer.activation) {
+                Some(op) => {
+                    node(
+                        graph,
+                        "Gemm",
+                        &gemm,
+                        &[&input, &weight, &bias],
+                        &gemm,
+                        &[("transB", 1)],
+                    );
+                    node(
+                        graph,
+                        op,
+                        &format!("layers.{index}.{}", layer.activation.name()),
+                        &[&gemm],
+                        &output,
+                        &[],
+                    );
+                }
+                None => node(
+                    graph,
+                    "Gemm",
+                    &gemm,
+                    &[&input, &weight, &bias],
+                    &output,
+                    &[("transB", 1)],
+                ),
+            }
+            input = output;
+        }
+
+        graph.string(2, "neural");
+
+        for (index, layer) in nn.layers().iter().enumerate() {
+            let (rows, columns) = layer.weights.size();
+            initializer(
+                graph,
+                &format!("layers.{index}.weight"),
+                &[rows, columns],
+                &layer.weights.to_vec_rm(),
+            );
+            initializer(
+                graph,
+                &format!("layers.{index}.bias"),
+                &[rows],
+                &layer.bias.to_vec(),
+            );
+        }
+
+        value_info(graph, 11, "input", nn.n_input());
+        value_info(graph, 12, "output", nn.n_output());
+    });
+
+    model.message(8, |opset| {
+        opset.string(1, "");
+        opset.int64(2, OPSET_VERSION);
+    });
+
+    model.buf
+}
+
+pub fn write<W: Write>(nn: &NeuralNetwork, writer: &mut W) -> io::Result<()> {
+    writer.write_all(&to_bytes(nn))
+}
+
+/// Row-major tensor data converted to `f32`.
+struct Tensor {
+    dims: Vec<usize>,
+    data: Vec<f32>,
+}
+
+#[derive(Default)]
+struct Node {
+    name: String,
+    op_type: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    ints: HashMap<String, i64>,
+    floats: HashMap<String, f32>,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    initializers: HashMap<String, Tensor>,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+fn string(value: Value) -> Result<String, Error> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| Error::Decode("expected a string field".to_string()))
+}
+
+fn bytes(value: Value<'_>) -> Result<&[u8], Error> {
+    value
+        .as_bytes()
+        .ok_or_else(|| Error::Decode("expected a length delimited field".to_string()))
+}
+
+fn varint(value: Value) -> Result<u64, Error> {
+    value
+        .as_u64()
+        .ok_or_else(|| Error::Decode("expected a varint field".to_string()))
+}
+
+fn parse_value_info_name(buf: &[u8]) -> Result<String, Error> {
+    for field in Decoder::new(buf) {
+        if let (1, value) = field? {
+            return string(value);
+        }
+    }
+    Err(Error::Decode("value info without a name".to_string()))
+}
+
+fn parse_tensor(buf: &[u8]) -> Result<(String, Tensor), Error> {
+    let mut name = String::new();
+    let mut dims = Vec::new();
+    let mut data_type = 0;
+    let mut raw = None;
+    let mut floats = Vec::new();
+
+    for field in Decoder::new(buf) {
+        match field? {
+            (1, value) => dims.extend(Decoder::varints(value)?.into_iter().map(|d| d as usize)),
+            (2, value) => data_type = varint(value)? as i64,
+            (4, Value::Fixed32(b)) => floats.push(f32::from_le_bytes(b)),
+            (4, Value::Bytes(b)) => floats.extend(
+                b.chunks_exact(4)
+                    .map(|b| f32::from_le_bytes(b.try_into().unwrap())),
+            ),
+            (8, value) => name = string(value)?,
+            (9, value) => raw = Some(bytes(value)?),
+            (10, Value::Fixed64(b)) => floats.push(f64::from_le_bytes(b) as f32),
+            (10, Value::Bytes(b)) => floats.extend(
+                b.chunks_exact(8)
+                    .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32),
+            ),
+            (14, value) if varint(value)? != 0 => {
+                return Err(Error::UnsupportedTensor(format!(
+                    "`{name}` is stored as external data"
+                )))
+            }
+            _ => {}
+        }
+    }
+
+    let data = match (data_type, raw) {
+        (FLOAT, Some(raw)) => raw
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect(),
+        (DOUBLE, Some(raw)) => raw
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
+            .collect(),
+        (FLOAT | DOUBLE, None) => floats,
+        _ => {
+            return Err(Error::UnsupportedTensor(format!(
+                "`{name}` has data type {data_type}, expected float or double"
+            )))
+        }
+    };
+
+    if dims.contains(&0) {
+        return Err(Error::UnsupportedTensor(format!(
+            "`{name}` has dims {dims:?} with a zero dimension"
+        )));
+    }
+    let len = dims.iter().try_fold(1usize, |len, d| len.checked_mul(*d));
+    if len != Some(data.len()) {
+        return Err(Error::UnsupportedTensor(format!(
+            "`{name}` has dims {dims:?} but {} elements",
+            data.len()
+        )));
+    }
+
+    Ok((name, Tensor { dims, data }))
+}
+
+fn parse_node(buf: &[u8]) -> Result<Node, Error> {
+    let mut node = Node::default();
+    for field in Decoder::new(buf) {
+        match field? {
+            (1, value) => node.inputs.push(string(value)?),
+            (2, value) => node.outputs.push(string(value)?),
+            (3, value) => node.name = string(value)?,
+            (4, value) => node.op_type = string(value)?,
+            (5, value) => {
+                let mut name = String::new();
+                let mut float = None;
+                let mut int = None;
+                for field in Decoder::new(bytes(value)?) {
+                    match field? {
+                        (1, value) => name = string(value)?,
+                        (2, Value::Fixed32(b)) => float = Some(f32::from_le_bytes(b)),
+                        (3, value) => int = Some(varint(value)? as i64),
+                        _ => {}
+                    }
+                }
+                if let Some(float) = float {
+                    node.floats.insert(name.clone(), float);
+                }
+                if let Some(int) = int {
+                    node.ints.insert(name, int);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(node)
+}
+
+fn parse_graph(buf: &[u8]) -> Result<Graph, Error> {
+    let mut graph = Graph::default();
+    for field in Decoder::new(buf) {
+        match field? {
+            (1, value) => graph.nodes.push(parse_node(bytes(value)?)?),
+            (5, value) => {
+                let (name, tensor) = parse_tensor(bytes(value)?)?;
+                graph.initializers.insert(name, tensor);
+            }
+            (11, value) => graph.inputs.push(parse_value_info_name(bytes(value)?)?),
+            (12, value) => graph.outputs.push(parse_value_info_name(bytes(value)?)?),
+            _ => {}
+        }
+    }
+    Ok(graph)
+}
+
+/// Returns `(rows, columns)` of a two dimensional tensor.
+fn matrix_dims(tensor: &Tensor, name: &str) -> Result<(usize, usize), Error> {
+    match tensor.dims[..] {
+        [rows, columns] => Ok((rows, columns)),
+        _ => Err(Error::InvalidGraph(format!(
+            "weight `{name}` has dims {:?}, expected two dimensions",
+            tensor.dims
+        ))),
+    }
+}
+
+/// A dense layer recognised in the graph, with row-major
+/// `(outputs, inputs)` weights.
+struct Dense<'a> {
+    rows: usize,
+    columns: usize,
+    weights: Vec<f32>,
+    bias: Vec<f32>,
+    output: &'a str,
+}
+
+struct Chain<'a> {
+    graph: &'a Graph,
+    consumers: HashMap<&'a str, Vec<usize>>,
+    visited: Vec<bool>,
+    opset: i64,
+}
+
+impl<'a> Chain<'a> {
+    fn initializer(&self, name: &str) -> Result<&'a Tensor, Error> {
+        self.graph
+            .initializers
+            .get(name)
+            .ok_or_else(|| Error::InvalidGraph(format!("`{name}` is not an initializer")))
+    }
+
+    /// The node reading `tensor`, if exactly one does. Reaching a node a
+    /// second time means the graph has a cycle.
+    fn next(&mut self, tensor: &str) -> Result<Option<&'a Node>, Error> {
+        match self.consumers.get(tensor).map(Vec::as_slice) {
+            None | Some([]) => Ok(None),
+            Some([index]) => {
+                let node = &self.graph.nodes[*index];
+                if self.visited[*index] {
+                    return Err(Error::InvalidGraph(format!(
+                        "{} `{}` is part of a cycle",
+                        node.op_type, node.name
+                    )));
+                }
+                self.visited[*index] = true;
+                Ok(Some(node))
+            }
+            Some(_) => Err(Error::InvalidGraph(format!(
+                "`{tensor}` is read by more than one node"
+            ))),
+        }
+    }
+
+    fn peek(&self, tensor: &str) -> Option<&'a Node> {
+        match self.consumers.get(tensor).map(Vec::as_slice) {
+            Some([index]) => Some(&self.graph.nodes[*index]),
+            _ => None,
+        }
+    }
+
+    /// Reads a bias of `rows` elements, scaled by `scale`.
+    fn bias(&self, name: &str, rows: usize, scale: f32) -> Result<Vec<f32>, Error> {
+        let bias = self.initializer(name)?;
+        if bias.data.len() != rows || bias.dims.iter().filter(|d| **d != 1).count() > 1 {
+            return Err(Error::InvalidGraph(format!(
+                "bias `{name}` has dims {:?}, expected [{rows}]",
+                bias.dims
+            )));
+        }
+        Ok(bias.data.iter().map(|v| v * scale).collect())
+    }
+
+    /// Consumes a `Gemm` or `MatMul` (+ `Add`) node reading `input`.
+    fn linear(&mut self, node: &'a Node, input: &str) -> Result<Dense<'a>, Error> {
+        let output = node
+            .outputs
+            .first()
+            .ok_or_else(|| Error::InvalidGraph(format!("node `{}` has no output", node.name)))?;
+
+        match node.op_type.as_str() {
+            "Gemm" => {
+                if node.inputs.first().map(String::as_str) != Some(input) {
+                    return Err(Error::InvalidGraph(format!(
+                        "Gemm `{}` must take the previous layer as its first input",
+                        node.name
+                    )));
+                }
+                if node.ints.get("transA").copied().unwrap_or(0) != 0 {
+                    return Err(Error::InvalidGraph(format!(
+                        "Gemm `{}` transposes its input",
+                        node.name
+                    )));
+                }
+                let alpha = node.floats.get("alpha").copied().unwrap_or(1.0);
+                let beta = node.floats.get("beta").copied().unwrap_or(1.0);
+                let trans_b = node.ints.get("transB").copied().unwrap_or(0) != 0;
+
+                let weight_name = node.inputs.get(1).ok_or_else(|| {
+                    Error::InvalidGraph(format!("Gemm `{}` has no weights", node.name))
+                })?;
+                let weight = self.initializer(weight_name)?;
+                let (rows, columns) = matrix_dims(weight, weight_name)?;
+                let (rows, columns, weights) = if trans_b {
+                    (
+                        rows,
+                        columns,
+                        weight.data.iter().map(|v| v * alpha).collect(),
+                    )
+                } else {
+                    (columns, rows, transpose(&weight.data, rows, columns, alpha))
+                };
+
+                let bias = match node.inputs.get(2).filter(|name| !name.is_empty()) {
+                    Some(name) => self.bias(name, rows, beta)?,
+                    None => vec![0.0; rows],
+                };
+
+                Ok(Dense {
+                    rows,
+                    columns,
+                    weights,
+                    bias,
+                    output,
+                })
+            }
+            "MatMul" => {
+                let weight_name = match &node.inputs[..] {
+                    [first, weight] if first == input => weight,
+                    _ => {
+                        return Err(Error::InvalidGraph(format!(
+                            "MatMul `{}` must multiply the previous layer by a weight",
+                            node.name
+                        )))
+                    }
+                };
+                let weight = self.initializer(weight_name)?;
+                let (rows, columns) = matrix_dims(weight, weight_name)?;
+                let mut dense = Dense {
+                    rows: columns,
+                    columns: rows,
+                    weights: transpose(&weight.data, rows, columns, 1.0),
+                    bias: vec![0.0; columns],
+                    output,
+                };
+
+                match self.peek(output) {
+                    Some(add) if add.op_type == "Add" => {
+                        self.next(output)?;
+                        let bias_name = match &add.inputs[..] {
+                            [a, b] if a == output => b,
+                            [a, b] if b == output => a,
+                            _ => {
+                                return Err(Error::InvalidGraph(format!(
+                                    "Add `{}` must add a bias to the MatMul output",
+                                    add.name
+                                )))
+                            }
+                        };
+                        dense.bias = self.bias(bias_name, dense.rows, 1.0)?;
+                        dense.output = add.outputs.first().ok_or_else(|| {
+                            Error::InvalidGraph(format!("node `{}` has no output", add.name))
+                        })?;
+                        Ok(dense)
+                    }
+                    _ => Ok(dense),
+                }
+            }
+            op => Err(Error::InvalidGraph(format!(
+                "expected Gemm or MatMul but found {op} `{}`",
+                node.name
+            ))),
+        }
+    }
+
+    fn activation(&self, node: &Node) -> Result<Option<Activation>, Error> {
+        Ok(match node.op_type.as_str() {
+            "Relu" => Some(Activation::Relu),
+            "Sigmoid" => Some(Activation::Sigmoid),
+            "Tanh" => Some(Activation::Tanh),
+            "Softmax" => {
+                let default = if self.opset < 13 { 1 } else { -1 };
+                let axis = node.ints.get("axis").copied().unwrap_or(default);
+                if axis != 1 && axis != -1 {
+                    return Err(Error::InvalidGraph(format!(
+                        "Softmax `{}` is over axis {axis} instead of the features",
+                        node.name
+                    )));
+                }
+                Some(Activation::Softmax)
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Transposes row-major `(rows, columns)` data, scaling it by `scale`.
+fn transpose(data: &[f32], rows: usize, columns: usize, scale: f32) -> Vec<f32> {
+    let mut res = Vec::with_capacity(data.len());
+    for column in 0..columns {
+        for row in 0..rows {
+            res.push(data[row * columns + column] * scale);
+        }
+    }
+    res
+}
+
+pub fn from_bytes(buf: &[u8]) -> Result<NeuralNetwork, Error> {
+    let mut graph = None;
+    let mut opset = OPSET_VERSION;
+    for field in Decoder::new(buf) {
+        match field? {
+            (7, value) => graph = Some(parse_graph(bytes(value)?)?),
+            (8, value) => {
+                let mut domain = String::new();
+                let mut version = 0;
+                for field in Decoder::new(bytes(value)?) {
+                    match field? {
+                        (1, value) => domain = string(value)?,
+                        (2, value) => version = varint(value)? as i64,
+                        _ => {}
+                    }
+                }
+                if domain.is_empty() || domain == "ai.onnx" {
+                    opset = version;
+                }
+            }
+            _ => {}
+        }
+    }
+    let graph = graph.ok_or_else(|| Error::Decode("model has no graph".to_string()))?;
+
+    let mut unsupported = graph
+        .nodes
+        .iter()
+        .map(|node| node.op_type.clone())
+        .filter(|op| !SUPPORTED_OPERATORS.contains(&op.as_str()))
+        .collect::<Vec<String>>();
+    if !unsupported.is_empty() {
+        unsupported.sort();
+        unsupported.dedup();
+        return Err(Error::UnsupportedOperators(unsupported));
+    }
+
+    let inputs = graph
+        .inputs
+        .iter()
+        .filter(|name| !graph.initializers.contains_key(*name))
+        .collect::<Vec<&String>>();
+    let input = match inputs[..] {
+        [input] => input.as_str(),
+        _ => {
+            return Err(Error::InvalidGraph(format!(
+                "expected one input, found {}",
+                inputs.len()
+            )))
+        }
+    };
+    let output = match &graph.outputs[..] {
+        [output] => output.as_str(),
+        _ => {
+            return Err(Error::InvalidGraph(format!(
+                "expected one output, found {}",
+                graph.outputs.len()
+            )))
+        }
+    };
+
+    let mut consumers: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, node) in graph.nodes.iter().enumerate() {
+        for name in &node.inputs {
+            consumers.entry(name.as_str()).or_default().push(index);
+        }
+    }
+    let mut chain = Chain {
+        graph: &graph,
+        consumers,
+        visited: vec![false; graph.nodes.len()],
+        opset,
+    };
+
+    // Layers are only uploaded once the whole graph has been checked
+    let mut layers: Vec<(Dense, Activation)> = Vec::new();
+    let mut current = input;
+    while current != output {
+        let node = chain.next(current)?.ok_or_else(|| {
+            Error::InvalidGraph(format!(
+                "`{current}` does not lead to the output `{output}`"
+            ))
+        })?;
+        let dense = chain.linear(node, current)?;
+        if let Some((prev, _)) = layers.last() {
+            if prev.rows != dense.columns {
+                return Err(Error::InvalidGraph(format!(
+                    "`{}` takes {} inputs but the previous layer has {} outputs",
+                    node.name, dense.columns, prev.rows
+                )));
+            }
+        }
+
+        current = dense.output;
+        let mut activation = Activation::Identity;
+        if let Some(next) = chain.peek(current) {
+            if let Some(a) = chain.activation(next)? {
+                chain.next(current)?;
+                activation = a;
+                current = next.outputs.first().ok_or_else(|| {
+                    Error::InvalidGraph(format!("node `{}` has no output", next.name))
+                })?;
+            }
+        }
+
+        layers.push((dense, activation));
+    }
+
+    if let Some(index) = chain.visited.iter().position(|visited| !visited) {
+        let node = &graph.nodes[index];
+        return Err(Error::InvalidGraph(format!(
+            "{} `{}` is not part of the layer chain",
+            node.op_type, node.name
+        )));
+    }
+    if layers.is_empty() {
+        return Err(Error::InvalidGraph("graph has no layers".to_string()));
+    }
+
+    Ok(NeuralNetwork::from_layers(
+        layers
+            .into_iter()
+            .map(|(dense, activation)| {
+                Layer::new(
+                    Matrix::from_slice_rm(&dense.weights, dense.rows, dense.columns),
+                    Matrix::from_slice_cm(&dense.bias, dense.rows, 1),
+                    activation,
+                )
+            })
+            .collect(),
+    ))
+}
+
+pub fn read<R: Read>(reader: &mut R) -> Result<NeuralNetwork, Error> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    from_bytes(&buf)
+}
@@ -0,0 +1,161 @@
+//! Minimal protocol buffers wire format encoder.
+
+const VARINT: u32 = 0;
+const LENGTH_DELIMITED: u32 = 2;
+
+#[derive(Default)]
+pub(crate) struct Encoder {
+    pub buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.buf.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        self.buf.push(v as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u32) {
+        self.varint(((field << 3) | wire_type) as u64);
+    }
+
+    pub fn int64(&mut self, field: u32, v: i64) {
+        self.key(field, VARINT);
+        self.varint(v as u64);
+    }
+
+    pub fn bytes(&mut self, field: u32, v: &[u8]) {
+        self.key(field, LENGTH_DELIMITED);
+        self.varint(v.len() as u64);
+        self.buf.extend_from_slice(v);
+    }
+
+    pub fn string(&mut self, field: u32, v: &str) {
+        self.bytes(field, v.as_bytes());
+    }
+
+    /// Encodes a nested message built by `f`.
+    pub fn message<F: FnOnce(&mut Encoder)>(&mut self, field: u32, f: F) {
+        let mut nested = Encoder::default();
+        f(&mut nested);
+        self.bytes(field, &nested.buf);
+    }
+}
+
+/// A single field value as it appears on the wire.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Value<'a> {
+    Varint(u64),
+    Fixed64([u8; 8]),
+    Bytes(&'a [u8]),
+    Fixed32([u8; 4]),
+}
+
+impl<'a> Value<'a> {
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Varint(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Value::Bytes(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+}
+
+/// Iterates over the fields of an encoded message.
+pub(crate) struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .buf
+                .get(self.pos)
+                .ok_or_else(|| "truncated varint".to_string())?;
+            self.pos += 1;
+            v |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err("varint is too long".to_string())
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| "truncated field".to_string())?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn field(&mut self) -> Result<(u32, Value<'a>), String> {
+        let key = self.varint()?;
+        let field = (key >> 3) as u32;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => Value::Fixed64(self.take(8)?.try_into().unwrap()),
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => Value::Fixed32(self.take(4)?.try_into().unwrap()),
+            wire_type => return Err(format!("unsupported wire type {wire_type}")),
+        };
+        Ok((field, value))
+    }
+
+    /// Decodes a packed repeated varint field, or a single unpacked element.
+    pub fn varints(value: Value<'a>) -> Result<Vec<u64>, String> {
+        match value {
+            Value::Varint(v) => Ok(vec![v]),
+            Value::Bytes(b) => {
+                let mut decoder = Decoder::new(b);
+                let mut values = Vec::new();
+                while decoder.pos < b.len() {
+                    values.push(decoder.varint()?);
+                }
+                Ok(values)
+            }
+            _ => Err("expected a varint field".to_string()),
+        }
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Result<(u32, Value<'a>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.buf.len() {
+            return None;
+        }
+        let field = self.field();
+        if field.is_err() {
+            // Stop after the first error instead of decoding garbage
+            self.pos = self.buf.len();
+        }
+        Some(field)
+    }
+}
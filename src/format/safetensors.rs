@@ -0,0 +1,222 @@
+//! [safetensors](https://github.com/huggingface/safetensors) import and export.
+//!
+//! Layer `i` is stored as `layers.{i}.weight` with shape `[outputs, inputs]`
+//! and `layers.{i}.bias` with shape `[outputs]`, both row major, matching the
+//! layout of PyTorch's `nn.Linear`. The header metadata holds the learning
+//! rate under `learning_rate` and each layer's activation under
+//! `layers.{i}.activation`. Files without activation metadata load as
+//! sigmoid layers. `F32` and `F64` tensors can be read.
+
+use std::{collections::HashMap, fmt, io};
+
+use ::safetensors::{tensor::TensorView, Dtype, SafeTensorError, SafeTensors};
+
+use crate::{
+    matrix::Matrix,
+    nn::{Activation, Layer, NeuralNetwork},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    SafeTensors(SafeTensorError),
+    MissingTensor(String),
+    UnsupportedDtype {
+        name: String,
+        dtype: Dtype,
+    },
+    ShapeMismatch {
+        name: String,
+        expected: Vec<usize>,
+        found: Vec<usize>,
+    },
+    UnknownActivation(String),
+    InvalidMetadata(String),
+    InvalidArchitecture(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::SafeTensors(e) => write!(f, "safetensors error: {e}"),
+            Error::MissingTensor(name) => write!(f, "missing tensor `{name}`"),
+            Error::UnsupportedDtype { name, dtype } => {
+                write!(f, "tensor `{name}` has unsupported dtype {dtype:?}")
+            }
+            Error::ShapeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "tensor `{name}` has shape {found:?}, expected {expected:?}"
+            ),
+            Error::UnknownActivation(name) => write!(f, "unknown activation `{name}`"),
+            Error::InvalidMetadata(msg) => write!(f, "invalid metadata: {msg}"),
+            Error::InvalidArchitecture(msg) => write!(f, "invalid architecture: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::SafeTensors(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<SafeTensorError> for Error {
+    fn from(e: SafeTensorError) -> Self {
+        Error::SafeTensors(e)
+    }
+}
+
+fn f32_bytes(v: &[f32]) -> Vec<u8> {
+    v.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub fn to_bytes(nn: &NeuralNetwork) -> Result<Vec<u8>, Error> {
+    let mut metadata = HashMap::new();
+    metadata.insert("learning_rate".to_string(), nn.learning_rate().to_string());
+
+    let mut buffers = Vec::new();
+    for (index, layer) in nn.layers().iter().enumerate() {
+        let (rows, columns) = layer.weights.size();
+        metadata.insert(
+            format!("layers.{index}.activation"),
+            layer.activation.name().to_string(),
+        );
+        buffers.push((
+            format!("layers.{index}.weight"),
+            vec![rows, columns],
+            f32_bytes(&layer.weights.to_vec_rm()),
+        ));
+        buffers.push((
+            format!("layers.{index}.bias"),
+            vec![rows],
+            f32_bytes(&layer.bias.to_vec()),
+        ));
+    }
+
+    let mut tensors = Vec::new();
+    for (name, shape, data) in &buffers {
+        tensors.push((
+            name.as_str(),
+            TensorView::new(Dtype::F32, shape.clone(), data)?,
+        ));
+    }
+
+    Ok(::safetensors::serialize(tensors, &Some(metadata))?)
+}
+
+fn read_tensor(
+    tensors: &SafeTensors,
+    name: &str,
+    expected: Option<&[usize]>,
+) -> Result<(Vec<usize>, Vec<f32>), Error> {
+    let view = tensors
+        .tensor(name)
+        .map_err(|_| Error::MissingTensor(name.to_string()))?;
+    let shape = view.shape().to_vec();
+    if let Some(expected) = expected {
+        if shape != expected {
+            return Err(Error::ShapeMismatch {
+                name: name.to_string(),
+                expected: expected.to_vec(),
+                found: shape,
+            });
+        }
+    }
+
+    let data = match view.dtype() {
+        Dtype::F32 => view
+            .data()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect(),
+        Dtype::F64 => view
+            .data()
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
+            .collect(),
+        dtype => {
+            return Err(Error::UnsupportedDtype {
+                name: name.to_string(),
+                dtype,
+            })
+        }
+    };
+
+    Ok((shape, data))
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<NeuralNetwork, Error> {
+    let (_, header) = SafeTensors::read_metadata(bytes)?;
+    let tensors = SafeTensors::deserialize(bytes)?;
+    let metadata = header.metadata().clone().unwrap_or_default();
+
+    let mut layers: Vec<Layer> = Vec::new();
+    while tensors
+        .tensor(&format!("layers.{}.weight", layers.len()))
+        .is_ok()
+    {
+        let index = layers.len();
+        let (shape, weights) = read_tensor(&tensors, &format!("layers.{index}.weight"), None)?;
+        if shape.len() != 2 {
+            return Err(Error::InvalidArchitecture(format!(
+                "layer {index} weight has shape {shape:?}, expected two dimensions"
+            )));
+        }
+        let (rows, columns) = (shape[0], shape[1]);
+        if rows == 0 || columns == 0 {
+            return Err(Error::InvalidArchitecture(format!(
+                "layer {index} has shape {rows}x{columns}"
+            )));
+        }
+        if let Some(prev) = layers.last() {
+            if prev.n_output() != columns {
+                return Err(Error::ShapeMismatch {
+                    name: format!("layers.{index}.weight"),
+                    expected: vec![rows, prev.n_output()],
+                    found: shape,
+                });
+            }
+        }
+        let (_, bias) = read_tensor(&tensors, &format!("layers.{index}.bias"), Some(&[rows]))?;
+
+        let activation = match metadata.get(&format!("layers.{index}.activation")) {
+            Some(name) => {
+                Activation::from_name(name).ok_or_else(|| Error::UnknownActivation(name.clone()))?
+            }
+            None => Activation::Sigmoid,
+        };
+
+        layers.push(Layer::new(
+            Matrix::from_slice_rm(&weights, rows, columns),
+            Matrix::from_slice_cm(&bias, rows, 1),
+            activation,
+        ));
+    }
+
+    if layers.is_empty() {
+        return Err(Error::MissingTensor("layers.0.weight".to_string()));
+    }
+
+    let mut nn = NeuralNetwork::from_layers(layers);
+    if let Some(lr) = metadata.get("learning_rate") {
+        nn.set_learning_rate(lr.parse().map_err(|_| {
+            Error::InvalidMetadata(format!("learning_rate `{lr}` is not a number"))
+        })?);
+    }
+    Ok(nn)
+}
@@ -0,0 +1,142 @@
+//! `serde` implementations for [`Matrix`], [`Layer`] and [`NeuralNetwork`].
+//!
+//! Matrices are represented by their shape and column-major data, layers by
+//! their parameters and activation. Reusable buffers are not serialized.
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    matrix::Matrix,
+    nn::{Activation, Layer, NeuralNetwork},
+};
+
+#[derive(Serialize)]
+#[serde(rename = "Matrix")]
+struct MatrixRef {
+    rows: usize,
+    columns: usize,
+    data: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Matrix")]
+struct MatrixRepr {
+    rows: usize,
+    columns: usize,
+    data: Vec<f32>,
+}
+
+impl Serialize for Matrix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (rows, columns) = self.size();
+        MatrixRef {
+            rows,
+            columns,
+            data: self.to_vec(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Matrix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = MatrixRepr::deserialize(deserializer)?;
+        if repr.rows.checked_mul(repr.columns) != Some(repr.data.len()) {
+            return Err(D::Error::custom(format!(
+                "matrix of shape {}x{} has {} elements",
+                repr.rows,
+                repr.columns,
+                repr.data.len()
+            )));
+        }
+        Ok(Matrix::from_slice_cm(&repr.data, repr.rows, repr.columns))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Layer")]
+struct LayerRef<'a> {
+    weights: &'a Matrix,
+    bias: &'a Matrix,
+    activation: Activation,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Layer")]
+struct LayerRepr {
+    weights: Matrix,
+    bias: Matrix,
+    activation: Activation,
+}
+
+impl Serialize for Layer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LayerRef {
+            weights: &self.weights,
+            bias: &self.bias,
+            activation: self.activation,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Layer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = LayerRepr::deserialize(deserializer)?;
+        if repr.bias.size() != (repr.weights.size().0, 1) {
+            return Err(D::Error::custom(format!(
+                "bias of shape {:?} does not match weights of shape {:?}",
+                repr.bias.size(),
+                repr.weights.size()
+            )));
+        }
+        Ok(Layer::new(repr.weights, repr.bias, repr.activation))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "NeuralNetwork")]
+struct NeuralNetworkRef<'a> {
+    learning_rate: f32,
+    layers: &'a [Layer],
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "NeuralNetwork")]
+struct NeuralNetworkRepr {
+    learning_rate: f32,
+    layers: Vec<Layer>,
+}
+
+impl Serialize for NeuralNetwork {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        NeuralNetworkRef {
+            learning_rate: self.learning_rate(),
+            layers: self.layers(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NeuralNetwork {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = NeuralNetworkRepr::deserialize(deserializer)?;
+        if repr.layers.is_empty() {
+            return Err(D::Error::custom("network has no layers"));
+        }
+        for (index, pair) in repr.layers.windows(2).enumerate() {
+            if pair[0].n_output() != pair[1].n_input() {
+                return Err(D::Error::custom(format!(
+                    "layer {} takes {} inputs but layer {index} has {} outputs",
+                    index + 1,
+                    pair[1].n_input(),
+                    pair[0].n_output()
+                )));
+            }
+        }
+
+        let mut nn = NeuralNetwork::from_layers(repr.layers);
+        nn.set_learning_rate(repr.learning_rate);
+        Ok(nn)
+    }
+}
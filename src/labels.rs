@@ -0,0 +1,142 @@
+//! Encoding class labels into targets and decoding network outputs back into
+//! classes.
+//!
+//! Matrices follow the batch layout of [`crate::data::DataLoader`]: one
+//! sample per column, one class per row.
+
+use std::collections::BTreeSet;
+
+use crate::matrix::Matrix;
+
+/// Maps arbitrary labels to class indices `0..classes` and back.
+///
+/// Classes are numbered in the sorted order of the labels seen by
+/// [`LabelEncoder::fit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LabelEncoder<T> {
+    classes: Vec<T>,
+}
+
+impl<T: Ord + Clone> LabelEncoder<T> {
+    pub fn fit<I: IntoIterator<Item = T>>(labels: I) -> Self {
+        Self {
+            classes: labels
+                .into_iter()
+                .collect::<BTreeSet<T>>()
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Labels in the order of their class indices.
+    pub fn classes(&self) -> &[T] {
+        &self.classes
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    /// Class index of a label, `None` if it was not seen when fitting.
+    pub fn encode(&self, label: &T) -> Option<usize> {
+        self.classes.binary_search(label).ok()
+    }
+
+    pub fn decode(&self, index: usize) -> Option<&T> {
+        self.classes.get(index)
+    }
+
+    /// One-hot targets for `labels`, one column per label.
+    pub fn one_hot(&self, labels: &[T]) -> Option<Matrix> {
+        let indices = labels
+            .iter()
+            .map(|label| self.encode(label))
+            .collect::<Option<Vec<usize>>>()?;
+        Some(one_hot(&indices, self.len()))
+    }
+}
+
+/// One-hot vector of a class index.
+pub fn one_hot_vec(index: usize, classes: usize) -> Vec<f32> {
+    assert!(
+        index < classes,
+        "class {index} out of range for {classes} classes"
+    );
+    let mut v = vec![0.0; classes];
+    v[index] = 1.0;
+    v
+}
+
+/// Multi-hot vector with every class in `indices` set.
+pub fn multi_hot_vec(indices: &[usize], classes: usize) -> Vec<f32> {
+    let mut v = vec![0.0; classes];
+    for index in indices {
+        assert!(
+            *index < classes,
+            "class {index} out of range for {classes} classes"
+        );
+        v[*index] = 1.0;
+    }
+    v
+}
+
+/// One-hot targets with one column per class index.
+pub fn one_hot(indices: &[usize], classes: usize) -> Matrix {
+    let mut data = Vec::with_capacity(indices.len() * classes);
+    for index in indices {
+        data.extend(one_hot_vec(*index, classes));
+    }
+    Matrix::from_slice_cm(&data, classes, indices.len())
+}
+
+/// Multi-hot targets with one column per set of class indices.
+pub fn multi_hot(indices: &[Vec<usize>], classes: usize) -> Matrix {
+    let mut data = Vec::with_capacity(indices.len() * classes);
+    for sample in indices {
+        data.extend(multi_hot_vec(sample, classes));
+    }
+    Matrix::from_slice_cm(&data, classes, indices.len())
+}
+
+/// Index of the largest output, the first one on ties.
+///
+/// Also recovers the class index of a one-hot target.
+pub fn argmax(output: &[f32]) -> usize {
+    assert!(!output.is_empty());
+    let mut best = 0;
+    for (i, v) in output.iter().enumerate() {
+        if *v > output[best] {
+            best = i;
+        }
+    }
+    best
+}
+
+/// The `k` largest outputs with their class indices, largest first.
+pub fn top_k(output: &[f32], k: usize) -> Vec<(usize, f32)> {
+    let mut classes = output.iter().copied().enumerate().collect::<Vec<_>>();
+    // Stable, so ties keep the lower class index first
+    classes.sort_by(|a, b| b.1.total_cmp(&a.1));
+    classes.truncate(k);
+    classes
+}
+
+/// Softmax of raw outputs, for networks whose last layer is not
+/// [`crate::nn::Activation::Softmax`].
+pub fn probabilities(output: &[f32]) -> Vec<f32> {
+    let max = output.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exp = output.iter().map(|v| (v - max).exp()).collect::<Vec<f32>>();
+    let sum = exp.iter().sum::<f32>();
+    exp.into_iter().map(|v| v / sum).collect()
+}
+
+/// Class index of every column of a batch of outputs.
+pub fn argmax_columns(outputs: &Matrix) -> Vec<usize> {
+    let (rows, _) = outputs.size();
+    outputs.to_vec().chunks_exact(rows).map(argmax).collect()
+}
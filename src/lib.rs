@@ -1,2 +1,8 @@
+pub mod checkpoint;
+pub mod data;
+pub mod format;
+pub mod labels;
 pub mod matrix;
+pub mod metrics;
 pub mod nn;
+pub mod preprocessing;
@@ -1,4 +1,10 @@
-use std::{ptr::null, sync::Arc};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+    ptr::null,
+    sync::Arc,
+};
 
 use cudarc::{
     cublas::{sys::lib, CudaBlas},
@@ -9,6 +15,8 @@ use cudarc::{
     nvrtc::compile_ptx,
 };
 
+use crate::format::npy;
+
 lazy_static::lazy_static! {
     static ref CUDA_DEV: Arc<CudaDevice> = CudaDevice::new(0).unwrap();
     static ref CUBLAS: CudaBlas = CudaBlas::new(Arc::clone(&CUDA_DEV)).unwrap();
@@ -42,6 +50,46 @@ pub fn init() {
     CUDA_DEV
         .load_ptx(dsigmoid_kernel, "dsigmoid", &["dsigmoid"])
         .unwrap();
+
+    println!("[matrix::init] Compiling kernel `relu`...");
+    let relu_kernel = compile_ptx(include_str!("../kernels/relu.cu")).unwrap();
+    println!("[matrix::init] Loading kernel `relu`...");
+    CUDA_DEV.load_ptx(relu_kernel, "relu", &["relu"]).unwrap();
+
+    println!("[matrix::init] Compiling kernel `drelu`...");
+    let drelu_kernel = compile_ptx(include_str!("../kernels/drelu.cu")).unwrap();
+    println!("[matrix::init] Loading kernel `drelu`...");
+    CUDA_DEV
+        .load_ptx(drelu_kernel, "drelu", &["drelu"])
+        .unwrap();
+
+    println!("[matrix::init] Compiling kernel `mat_tanh`...");
+    let mat_tanh_kernel = compile_ptx(include_str!("../kernels/mat_tanh.cu")).unwrap();
+    println!("[matrix::init] Loading kernel `mat_tanh`...");
+    CUDA_DEV
+        .load_ptx(mat_tanh_kernel, "mat_tanh", &["mat_tanh"])
+        .unwrap();
+
+    println!("[matrix::init] Compiling kernel `dtanh`...");
+    let dtanh_kernel = compile_ptx(include_str!("../kernels/dtanh.cu")).unwrap();
+    println!("[matrix::init] Loading kernel `dtanh`...");
+    CUDA_DEV
+        .load_ptx(dtanh_kernel, "dtanh", &["dtanh"])
+        .unwrap();
+
+    println!("[matrix::init] Compiling kernel `softmax`...");
+    let softmax_kernel = compile_ptx(include_str!("../kernels/softmax.cu")).unwrap();
+    println!("[matrix::init] Loading kernel `softmax`...");
+    CUDA_DEV
+        .load_ptx(softmax_kernel, "softmax", &["softmax"])
+        .unwrap();
+
+    println!("[matrix::init] Compiling kernel `dsoftmax`...");
+    let dsoftmax_kernel = compile_ptx(include_str!("../kernels/dsoftmax.cu")).unwrap();
+    println!("[matrix::init] Loading kernel `dsoftmax`...");
+    CUDA_DEV
+        .load_ptx(dsoftmax_kernel, "dsoftmax", &["dsoftmax"])
+        .unwrap();
 }
 
 #[derive(Debug, Clone)]
@@ -87,24 +135,70 @@ impl Matrix {
 
         Self {
             cudata,
-            rows: rows,
-            columns: columns,
+            rows,
+            columns,
         }
     }
 
+    /// From slice row major
+    pub fn from_slice_rm(v: &[f32], rows: usize, columns: usize) -> Self {
+        assert_eq!(v.len(), rows * columns);
+
+        let mut cm = Vec::with_capacity(v.len());
+        for column in 0..columns {
+            for row in 0..rows {
+                cm.push(v[row * columns + column]);
+            }
+        }
+
+        Self::from_slice_cm(&cm, rows, columns)
+    }
+
     pub fn to_vec(&self) -> Vec<f32> {
         CUDA_DEV.dtoh_sync_copy(&self.cudata).unwrap()
     }
 
+    /// To vec row major
+    pub fn to_vec_rm(&self) -> Vec<f32> {
+        let cm = self.to_vec();
+
+        let mut rm = Vec::with_capacity(cm.len());
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                rm.push(cm[column * self.rows + row]);
+            }
+        }
+
+        rm
+    }
+
+    /// Reads a `.npy` file, see [`crate::format::npy`].
+    pub fn read_npy<P: AsRef<Path>>(path: P) -> Result<Self, npy::Error> {
+        npy::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn write_npy<P: AsRef<Path>>(&self, path: P) -> Result<(), npy::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        npy::write(self, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
     pub fn size(&self) -> (usize, usize) {
         (self.rows, self.columns)
     }
 
+    pub fn zero(&mut self) {
+        CUDA_DEV.memset_zeros(&mut self.cudata).unwrap();
+
+        CUDA_DEV.synchronize().unwrap();
+    }
+
     pub fn multiply_scalar(&mut self, n: f32) {
         unsafe {
             lib()
                 .cublasSscal_v2(
-                    CUBLAS.handle().clone(),
+                    *CUBLAS.handle(),
                     self.cudata.len() as i32,
                     (&n) as *const f32 as *const _,
                     *self.cudata.device_ptr_mut() as *mut _,
@@ -122,14 +216,11 @@ impl Matrix {
             .get_func("mat_add_scalar", "mat_add_scalar")
             .unwrap();
 
-        let cfg = LaunchConfig {
-            grid_dim: (1, 1, 1),
-            block_dim: (self.cudata.len() as u32, 1, 1),
-            shared_mem_bytes: 0,
-        };
+        let len = self.cudata.len() as u32;
+        let cfg = LaunchConfig::for_num_elems(len);
 
         unsafe {
-            f.launch(cfg, (&self.cudata, n)).unwrap();
+            f.launch(cfg, (&self.cudata, n, len)).unwrap();
         }
 
         CUDA_DEV.synchronize().unwrap();
@@ -143,14 +234,11 @@ impl Matrix {
 
         let mut r = Matrix::new(self.rows, self.columns);
 
-        let cfg = LaunchConfig {
-            grid_dim: (1, 1, 1),
-            block_dim: (self.cudata.len() as u32, 1, 1),
-            shared_mem_bytes: 0,
-        };
+        let n = self.cudata.len() as u32;
+        let cfg = LaunchConfig::for_num_elems(n);
 
         unsafe {
-            f.launch(cfg, (&self.cudata, &b.cudata, &mut r.cudata))
+            f.launch(cfg, (&self.cudata, &b.cudata, &mut r.cudata, n))
                 .unwrap();
         }
 
@@ -166,7 +254,7 @@ impl Matrix {
         unsafe {
             lib()
                 .cublasSdgmm(
-                    CUBLAS.handle().clone(),
+                    *CUBLAS.handle(),
                     cudarc::cublas::sys::cublasSideMode_t::CUBLAS_SIDE_LEFT,
                     self.rows as i32,
                     self.columns as i32,
@@ -191,15 +279,20 @@ impl Matrix {
     }
 
     pub fn add_matrix(&mut self, b: &Self) {
+        self.add_scaled_matrix(b, 1.0);
+    }
+
+    /// `self += alpha * b`
+    pub fn add_scaled_matrix(&mut self, b: &Self, alpha: f32) {
         #[cfg(debug_assertions)]
         assert_eq!(b.size(), self.size());
 
         unsafe {
             lib()
                 .cublasSaxpy_v2(
-                    CUBLAS.handle().clone(),
+                    *CUBLAS.handle(),
                     self.cudata.len() as i32,
-                    &1.0f32 as *const f32,
+                    &alpha as *const f32,
                     *b.cudata.device_ptr() as *const _,
                     1,
                     *self.cudata.device_ptr_mut() as *mut _,
@@ -221,7 +314,7 @@ impl Matrix {
         unsafe {
             lib()
                 .cublasSgemm_v2(
-                    CUBLAS.handle().clone(),
+                    *CUBLAS.handle(),
                     cudarc::cublas::sys::cublasOperation_t::CUBLAS_OP_N,
                     cudarc::cublas::sys::cublasOperation_t::CUBLAS_OP_N,
                     self.rows as i32,
@@ -232,7 +325,7 @@ impl Matrix {
                     self.rows as i32,
                     *b.cudata.device_ptr() as *const _,
                     b.rows as i32,
-                    &1.0f32 as *const f32,
+                    &0.0f32 as *const f32,
                     *res.cudata.device_ptr() as *mut _,
                     res.rows as i32,
                 )
@@ -256,7 +349,7 @@ impl Matrix {
         unsafe {
             lib()
                 .cublasSgeam(
-                    CUBLAS.handle().clone(),
+                    *CUBLAS.handle(),
                     cudarc::cublas::sys::cublasOperation_t::CUBLAS_OP_T,
                     cudarc::cublas::sys::cublasOperation_t::CUBLAS_OP_N,
                     self.columns as i32,
@@ -285,34 +378,102 @@ impl Matrix {
     }
 
     pub fn sigmoid(&mut self) {
-        let f = CUDA_DEV.get_func("sigmoid", "sigmoid").unwrap();
+        self.launch_elementwise("sigmoid");
+    }
 
-        let cfg = LaunchConfig {
-            grid_dim: (1, 1, 1),
-            block_dim: (self.cudata.len() as u32, 1, 1),
-            shared_mem_bytes: 0,
-        };
+    pub fn dsigmoid(&mut self, b: &mut Matrix) {
+        self.launch_derivative("dsigmoid", b);
+    }
+
+    fn launch_elementwise(&mut self, kernel: &str) {
+        let f = CUDA_DEV.get_func(kernel, kernel).unwrap();
+
+        let n = self.cudata.len() as u32;
+        let cfg = LaunchConfig::for_num_elems(n);
 
         unsafe {
-            f.launch(cfg, (&mut self.cudata,)).unwrap();
+            f.launch(cfg, (&mut self.cudata, n)).unwrap();
         }
 
         CUDA_DEV.synchronize().unwrap();
     }
 
-    pub fn dsigmoid(&mut self, b: &mut Matrix) {
-        let f = CUDA_DEV.get_func("dsigmoid", "dsigmoid").unwrap();
+    fn launch_derivative(&self, kernel: &str, b: &mut Matrix) {
+        let f = CUDA_DEV.get_func(kernel, kernel).unwrap();
+
+        let n = self.cudata.len() as u32;
+        let cfg = LaunchConfig::for_num_elems(n);
+
+        unsafe {
+            f.launch(cfg, (&self.cudata, &mut b.cudata, n)).unwrap();
+        }
+
+        CUDA_DEV.synchronize().unwrap();
+    }
+
+    pub fn relu(&mut self) {
+        self.launch_elementwise("relu");
+    }
 
-        let cfg = LaunchConfig {
-            grid_dim: (1, 1, 1),
-            block_dim: (self.cudata.len() as u32, 1, 1),
-            shared_mem_bytes: 0,
-        };
+    pub fn drelu(&self, b: &mut Matrix) {
+        self.launch_derivative("drelu", b);
+    }
+
+    pub fn tanh(&mut self) {
+        self.launch_elementwise("mat_tanh");
+    }
+
+    /// Assumes `self` comes from [`Self::tanh`]
+    pub fn dtanh(&self, b: &mut Matrix) {
+        self.launch_derivative("dtanh", b);
+    }
+
+    /// Softmax over all elements, meant for column vectors.
+    pub fn softmax(&mut self) {
+        let f = CUDA_DEV.get_func("softmax", "softmax").unwrap();
+        let n = self.cudata.len() as u32;
+        let cfg = reduction_config(self.cudata.len());
 
         unsafe {
-            f.launch(cfg, (&self.cudata, &mut b.cudata)).unwrap();
+            f.launch(cfg, (&mut self.cudata, n)).unwrap();
         }
 
         CUDA_DEV.synchronize().unwrap();
     }
+
+    /// Backpropagates `errors` through a softmax whose outputs are `self`
+    /// into `b`: `b_i = y_i * (e_i - sum_j y_j * e_j)`.
+    pub fn dsoftmax(&self, errors: &Matrix, b: &mut Matrix) {
+        #[cfg(debug_assertions)]
+        assert_eq!(self.size(), errors.size());
+
+        let f = CUDA_DEV.get_func("dsoftmax", "dsoftmax").unwrap();
+        let cfg = reduction_config(self.cudata.len());
+
+        unsafe {
+            f.launch(
+                cfg,
+                (
+                    &self.cudata,
+                    &errors.cudata,
+                    &mut b.cudata,
+                    self.cudata.len() as u32,
+                ),
+            )
+            .unwrap();
+        }
+
+        CUDA_DEV.synchronize().unwrap();
+    }
+}
+
+/// A single block for kernels reducing over all `len` elements: a power of
+/// two threads, at most 1024, with one float of shared memory each.
+fn reduction_config(len: usize) -> LaunchConfig {
+    let threads = len.next_power_of_two().min(1024) as u32;
+    LaunchConfig {
+        grid_dim: (1, 1, 1),
+        block_dim: (threads, 1, 1),
+        shared_mem_bytes: threads * std::mem::size_of::<f32>() as u32,
+    }
 }
@@ -0,0 +1,304 @@
+//! Metrics over predicted and actual class indices.
+//!
+//! Predictions and labels are class indices, as produced by
+//! `labels::argmax`. A [`ConfusionMatrix`] counts them by actual and
+//! predicted class and derives per-class and averaged precision, recall and
+//! F1, summarised by a [`ClassificationReport`]. Ratios with a zero
+//! denominator are 0.
+
+use std::fmt;
+
+use crate::labels::top_k;
+
+/// Share of predictions equal to the label.
+pub fn accuracy(predictions: &[usize], labels: &[usize]) -> f32 {
+    assert_eq!(predictions.len(), labels.len());
+    if labels.is_empty() {
+        return 0.0;
+    }
+    let correct = predictions
+        .iter()
+        .zip(labels)
+        .filter(|(p, l)| p == l)
+        .count();
+    correct as f32 / labels.len() as f32
+}
+
+/// Share of outputs whose `k` largest values include the label's class.
+pub fn top_k_accuracy<O: AsRef<[f32]>>(outputs: &[O], labels: &[usize], k: usize) -> f32 {
+    assert_eq!(outputs.len(), labels.len());
+    if labels.is_empty() {
+        return 0.0;
+    }
+    let correct = outputs
+        .iter()
+        .zip(labels)
+        .filter(|(output, label)| top_k(output.as_ref(), k).iter().any(|(c, _)| c == *label))
+        .count();
+    correct as f32 / labels.len() as f32
+}
+
+/// How per-class scores are combined into one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Average {
+    /// Unweighted mean over the classes
+    Macro,
+    /// Computed from the counts summed over all classes. For single-label
+    /// classification this equals the accuracy.
+    Micro,
+    /// Mean over the classes weighted by their support
+    Weighted,
+}
+
+/// Counts of samples by actual and predicted class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfusionMatrix {
+    classes: usize,
+    /// Row-major, rows are actual classes
+    counts: Vec<usize>,
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f32 / denominator as f32
+    }
+}
+
+pub(super) fn f1(precision: f32, recall: f32) -> f32 {
+    if precision + recall == 0.0 {
+        0.0
+    } else {
+        2.0 * precision * recall / (precision + recall)
+    }
+}
+
+impl ConfusionMatrix {
+    pub fn new(classes: usize) -> Self {
+        assert!(classes > 0);
+        Self {
+            classes,
+            counts: vec![0; classes * classes],
+        }
+    }
+
+    pub fn from_predictions(labels: &[usize], predictions: &[usize], classes: usize) -> Self {
+        assert_eq!(predictions.len(), labels.len());
+        let mut matrix = Self::new(classes);
+        for (label, prediction) in labels.iter().zip(predictions) {
+            matrix.add(*label, *prediction);
+        }
+        matrix
+    }
+
+    pub fn add(&mut self, actual: usize, predicted: usize) {
+        assert!(actual < self.classes && predicted < self.classes);
+        self.counts[actual * self.classes + predicted] += 1;
+    }
+
+    pub fn classes(&self) -> usize {
+        self.classes
+    }
+
+    pub fn count(&self, actual: usize, predicted: usize) -> usize {
+        self.counts[actual * self.classes + predicted]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    fn true_positives(&self, class: usize) -> usize {
+        self.count(class, class)
+    }
+
+    /// Number of samples predicted as `class`.
+    fn predicted(&self, class: usize) -> usize {
+        (0..self.classes).map(|a| self.count(a, class)).sum()
+    }
+
+    /// Number of samples of `class`.
+    pub fn support(&self, class: usize) -> usize {
+        (0..self.classes).map(|p| self.count(class, p)).sum()
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        let correct = (0..self.classes).map(|c| self.true_positives(c)).sum();
+        ratio(correct, self.total())
+    }
+
+    pub fn precision(&self, class: usize) -> f32 {
+        ratio(self.true_positives(class), self.predicted(class))
+    }
+
+    pub fn recall(&self, class: usize) -> f32 {
+        ratio(self.true_positives(class), self.support(class))
+    }
+
+    pub fn f1(&self, class: usize) -> f32 {
+        f1(self.precision(class), self.recall(class))
+    }
+
+    fn average<F: Fn(usize) -> f32>(&self, average: Average, score: F) -> f32 {
+        match average {
+            Average::Macro => (0..self.classes).map(score).sum::<f32>() / self.classes as f32,
+            Average::Weighted => {
+                let weighted = (0..self.classes)
+                    .map(|c| score(c) * self.support(c) as f32)
+                    .sum::<f32>();
+                ratio(1, self.total()) * weighted
+            }
+            // Every false positive of one class is a false negative of
+            // another, so micro precision, recall and F1 coincide
+            Average::Micro => self.accuracy(),
+        }
+    }
+
+    pub fn precision_average(&self, average: Average) -> f32 {
+        self.average(average, |c| self.precision(c))
+    }
+
+    pub fn recall_average(&self, average: Average) -> f32 {
+        self.average(average, |c| self.recall(c))
+    }
+
+    pub fn f1_average(&self, average: Average) -> f32 {
+        self.average(average, |c| self.f1(c))
+    }
+
+    /// Agreement between labels and predictions corrected for the agreement
+    /// expected by chance.
+    pub fn cohen_kappa(&self) -> f32 {
+        let total = self.total() as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+        let observed = self.accuracy() as f64;
+        let expected = (0..self.classes)
+            .map(|c| self.support(c) as f64 * self.predicted(c) as f64)
+            .sum::<f64>()
+            / (total * total);
+        if expected == 1.0 {
+            return 0.0;
+        }
+        ((observed - expected) / (1.0 - expected)) as f32
+    }
+
+    pub fn report(&self) -> ClassificationReport {
+        ClassificationReport {
+            names: (0..self.classes).map(|c| c.to_string()).collect(),
+            classes: (0..self.classes)
+                .map(|c| ClassScores {
+                    precision: self.precision(c),
+                    recall: self.recall(c),
+                    f1: self.f1(c),
+                    support: self.support(c),
+                })
+                .collect(),
+            accuracy: self.accuracy(),
+            macro_average: self.scores(Average::Macro),
+            weighted_average: self.scores(Average::Weighted),
+            cohen_kappa: self.cohen_kappa(),
+        }
+    }
+
+    fn scores(&self, average: Average) -> ClassScores {
+        ClassScores {
+            precision: self.precision_average(average),
+            recall: self.recall_average(average),
+            f1: self.f1_average(average),
+            support: self.total(),
+        }
+    }
+}
+
+impl fmt::Display for ConfusionMatrix {
+    /// Rows are actual classes, columns predicted classes.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .counts
+            .iter()
+            .max()
+            .map_or(1, |m| m.to_string().len())
+            .max(self.classes.to_string().len());
+        write!(f, "{:>width$}", "")?;
+        for p in 0..self.classes {
+            write!(f, " {p:>width$}")?;
+        }
+        for a in 0..self.classes {
+            write!(f, "\n{a:>width$}")?;
+            for p in 0..self.classes {
+                write!(f, " {:>width$}", self.count(a, p))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassScores {
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+    pub support: usize,
+}
+
+/// Per-class and averaged scores, printed as a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassificationReport {
+    /// Name of every class, its index unless set with
+    /// [`ClassificationReport::with_names`]
+    pub names: Vec<String>,
+    pub classes: Vec<ClassScores>,
+    pub accuracy: f32,
+    pub macro_average: ClassScores,
+    pub weighted_average: ClassScores,
+    pub cohen_kappa: f32,
+}
+
+impl ClassificationReport {
+    pub fn with_names<S: ToString>(mut self, names: &[S]) -> Self {
+        assert_eq!(names.len(), self.classes.len());
+        self.names = names.iter().map(|n| n.to_string()).collect();
+        self
+    }
+}
+
+impl fmt::Display for ClassificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .names
+            .iter()
+            .map(|n| n.len())
+            .chain(["weighted avg".len()])
+            .max()
+            .unwrap();
+        let row = |f: &mut fmt::Formatter<'_>, name: &str, s: &ClassScores| {
+            writeln!(
+                f,
+                "{name:>width$} {:>9.4} {:>9.4} {:>9.4} {:>9}",
+                s.precision, s.recall, s.f1, s.support
+            )
+        };
+
+        writeln!(
+            f,
+            "{:>width$} {:>9} {:>9} {:>9} {:>9}",
+            "", "precision", "recall", "f1-score", "support"
+        )?;
+        writeln!(f)?;
+        for (name, scores) in self.names.iter().zip(&self.classes) {
+            row(f, name, scores)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>width$} {:>9} {:>9} {:>9.4} {:>9}",
+            "accuracy", "", "", self.accuracy, self.macro_average.support
+        )?;
+        row(f, "macro avg", &self.macro_average)?;
+        row(f, "weighted avg", &self.weighted_average)?;
+        write!(f, "{:>width$} {:>29.4}", "cohen kappa", self.cohen_kappa)
+    }
+}
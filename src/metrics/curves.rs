@@ -0,0 +1,174 @@
+//! Threshold-independent evaluation of binary classifiers.
+//!
+//! Scores are the outputs of a single-output network, higher meaning more
+//! likely positive. A sample counts as predicted positive when its score is
+//! at least the threshold. Curves have one point per distinct score, ordered
+//! by descending threshold.
+
+use super::classification::f1;
+
+fn check(scores: &[f32], labels: &[bool]) {
+    assert_eq!(scores.len(), labels.len());
+    assert!(
+        labels.iter().any(|l| *l) && labels.iter().any(|l| !*l),
+        "labels need both positive and negative samples"
+    );
+}
+
+/// Thresholds with the true and false positives they give.
+fn counts(scores: &[f32], labels: &[bool]) -> Vec<(f32, usize, usize)> {
+    let mut order = (0..scores.len()).collect::<Vec<usize>>();
+    order.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+
+    let mut points = Vec::new();
+    let (mut tp, mut fp) = (0, 0);
+    for (i, index) in order.iter().enumerate() {
+        if labels[*index] {
+            tp += 1;
+        } else {
+            fp += 1;
+        }
+        // Samples with equal scores can't be separated by a threshold
+        if order
+            .get(i + 1)
+            .is_none_or(|next| scores[*next] != scores[*index])
+        {
+            points.push((scores[*index], tp, fp));
+        }
+    }
+    points
+}
+
+/// A threshold and the score it maximizes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    pub threshold: f32,
+    pub score: f32,
+}
+
+/// Receiver operating characteristic: true against false positive rate.
+///
+/// Starts at `(0, 0)` with an infinite threshold, so no sample is positive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RocCurve {
+    pub thresholds: Vec<f32>,
+    pub false_positive_rate: Vec<f32>,
+    pub true_positive_rate: Vec<f32>,
+}
+
+impl RocCurve {
+    pub fn new(scores: &[f32], labels: &[bool]) -> Self {
+        check(scores, labels);
+        let positives = labels.iter().filter(|l| **l).count() as f32;
+        let negatives = labels.len() as f32 - positives;
+
+        let mut curve = Self {
+            thresholds: vec![f32::INFINITY],
+            false_positive_rate: vec![0.0],
+            true_positive_rate: vec![0.0],
+        };
+        for (threshold, tp, fp) in counts(scores, labels) {
+            curve.thresholds.push(threshold);
+            curve.false_positive_rate.push(fp as f32 / negatives);
+            curve.true_positive_rate.push(tp as f32 / positives);
+        }
+        curve
+    }
+
+    /// Area under the curve by the trapezoidal rule.
+    pub fn auc(&self) -> f32 {
+        let x = &self.false_positive_rate;
+        let y = &self.true_positive_rate;
+        (1..x.len())
+            .map(|i| (x[i] - x[i - 1]) * (y[i] + y[i - 1]) / 2.0)
+            .sum()
+    }
+
+    /// The threshold maximizing Youden's J, the true minus the false positive
+    /// rate.
+    pub fn youden_threshold(&self) -> Threshold {
+        let mut best = Threshold {
+            threshold: self.thresholds[0],
+            score: f32::NEG_INFINITY,
+        };
+        for (i, threshold) in self.thresholds.iter().enumerate() {
+            let j = self.true_positive_rate[i] - self.false_positive_rate[i];
+            if j > best.score {
+                best = Threshold {
+                    threshold: *threshold,
+                    score: j,
+                };
+            }
+        }
+        best
+    }
+}
+
+/// Precision against recall.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrecisionRecallCurve {
+    pub thresholds: Vec<f32>,
+    pub precision: Vec<f32>,
+    pub recall: Vec<f32>,
+}
+
+impl PrecisionRecallCurve {
+    pub fn new(scores: &[f32], labels: &[bool]) -> Self {
+        check(scores, labels);
+        let positives = labels.iter().filter(|l| **l).count() as f32;
+
+        let mut curve = Self {
+            thresholds: Vec::new(),
+            precision: Vec::new(),
+            recall: Vec::new(),
+        };
+        for (threshold, tp, fp) in counts(scores, labels) {
+            curve.thresholds.push(threshold);
+            curve.precision.push(tp as f32 / (tp + fp) as f32);
+            curve.recall.push(tp as f32 / positives);
+        }
+        curve
+    }
+
+    /// Precision at every threshold weighted by the recall it adds, without
+    /// interpolation.
+    pub fn average_precision(&self) -> f32 {
+        let mut previous = 0.0;
+        let mut sum = 0.0;
+        for (precision, recall) in self.precision.iter().zip(&self.recall) {
+            sum += (recall - previous) * precision;
+            previous = *recall;
+        }
+        sum
+    }
+
+    /// The threshold maximizing the F1 score.
+    pub fn max_f1_threshold(&self) -> Threshold {
+        let mut best = Threshold {
+            threshold: self.thresholds[0],
+            score: f32::NEG_INFINITY,
+        };
+        for (i, threshold) in self.thresholds.iter().enumerate() {
+            let f1 = f1(self.precision[i], self.recall[i]);
+            if f1 > best.score {
+                best = Threshold {
+                    threshold: *threshold,
+                    score: f1,
+                };
+            }
+        }
+        best
+    }
+}
+
+/// Area under the ROC curve, the probability that a random positive sample
+/// scores higher than a random negative one.
+pub fn roc_auc(scores: &[f32], labels: &[bool]) -> f32 {
+    RocCurve::new(scores, labels).auc()
+}
+
+/// Summary of the precision-recall curve, see
+/// [`PrecisionRecallCurve::average_precision`].
+pub fn average_precision(scores: &[f32], labels: &[bool]) -> f32 {
+    PrecisionRecallCurve::new(scores, labels).average_precision()
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+use super::{
+    accuracy, average_precision, roc_auc, top_k_accuracy, ClassificationReport, ConfusionMatrix,
+    RegressionReport,
+};
+use crate::labels::argmax;
+
+/// A metric computed by [`crate::nn::NeuralNetwork::evaluate`].
+///
+/// Classes are the largest output and target value, or for networks with a
+/// single output whether the value is at least `0.5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Accuracy,
+    /// Share of samples whose class is among the `k` largest outputs. A
+    /// single output `p` counts as the two outputs `1 - p` and `p`.
+    TopKAccuracy(usize),
+    /// Confusion matrix based scores, see [`ClassificationReport`]
+    Classification,
+    /// Area under the ROC curve of a single-output network, NaN if the
+    /// samples are all of one class and `None` for several outputs
+    RocAuc,
+    /// Average precision of a single-output network, NaN if the samples are
+    /// all of one class and `None` for several outputs
+    AveragePrecision,
+    /// Per-output regression scores, see [`RegressionReport`]
+    Regression,
+}
+
+/// Results of evaluating a network on a dataset. Metrics that weren't
+/// requested are `None` or empty, and so are all metrics of an empty
+/// dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub samples: usize,
+    /// Mean squared error over all outputs, the loss the network trains on.
+    /// NaN for an empty dataset.
+    pub loss: f32,
+    pub accuracy: Option<f32>,
+    /// `(k, accuracy)` for every requested `k`
+    pub top_k_accuracy: Vec<(usize, f32)>,
+    pub classification: Option<ClassificationReport>,
+    pub roc_auc: Option<f32>,
+    pub average_precision: Option<f32>,
+    pub regression: Option<RegressionReport>,
+}
+
+fn class(values: &[f32]) -> usize {
+    if values.len() == 1 {
+        usize::from(values[0] >= 0.5)
+    } else {
+        argmax(values)
+    }
+}
+
+impl Report {
+    /// Computes `metrics` from outputs and targets given one sample at a
+    /// time.
+    pub fn new<O: AsRef<[f32]>, T: AsRef<[f32]>>(
+        outputs: &[O],
+        targets: &[T],
+        metrics: &[Metric],
+    ) -> Self {
+        assert_eq!(outputs.len(), targets.len());
+        if targets.is_empty() {
+            return Self {
+                samples: 0,
+                loss: f32::NAN,
+                accuracy: None,
+                top_k_accuracy: Vec::new(),
+                classification: None,
+                roc_auc: None,
+                average_precision: None,
+                regression: None,
+            };
+        }
+        let len = targets[0].as_ref().len();
+        assert!(outputs.iter().all(|o| o.as_ref().len() == len));
+        assert!(targets.iter().all(|t| t.as_ref().len() == len));
+
+        let squared_error = outputs
+            .iter()
+            .zip(targets)
+            .flat_map(|(o, t)| o.as_ref().iter().zip(t.as_ref()))
+            .map(|(o, t)| (o - t) * (o - t))
+            .sum::<f32>();
+        let mut report = Self {
+            samples: targets.len(),
+            loss: squared_error / (targets.len() * len) as f32,
+            accuracy: None,
+            top_k_accuracy: Vec::new(),
+            classification: None,
+            roc_auc: None,
+            average_precision: None,
+            regression: None,
+        };
+
+        let predictions = outputs
+            .iter()
+            .map(|o| class(o.as_ref()))
+            .collect::<Vec<_>>();
+        let labels = targets
+            .iter()
+            .map(|t| class(t.as_ref()))
+            .collect::<Vec<_>>();
+        let positive = labels.iter().map(|l| *l == 1).collect::<Vec<bool>>();
+        // The curves are only defined for binary classifiers, and undefined
+        // without both positive and negative samples
+        let both_classes = positive.contains(&true) && positive.contains(&false);
+        let curve = |metric: fn(&[f32], &[bool]) -> f32| {
+            if len != 1 {
+                None
+            } else if both_classes {
+                let scores = outputs.iter().map(|o| o.as_ref()[0]).collect::<Vec<f32>>();
+                Some(metric(&scores, &positive))
+            } else {
+                Some(f32::NAN)
+            }
+        };
+
+        for metric in metrics {
+            match *metric {
+                Metric::Accuracy => report.accuracy = Some(accuracy(&predictions, &labels)),
+                Metric::TopKAccuracy(k) => {
+                    let accuracy = if len == 1 {
+                        let outputs = outputs
+                            .iter()
+                            .map(|o| [1.0 - o.as_ref()[0], o.as_ref()[0]])
+                            .collect::<Vec<_>>();
+                        top_k_accuracy(&outputs, &labels, k)
+                    } else {
+                        top_k_accuracy(outputs, &labels, k)
+                    };
+                    report.top_k_accuracy.push((k, accuracy));
+                }
+                Metric::Classification => {
+                    let classes = if len == 1 { 2 } else { len };
+                    report.classification = Some(
+                        ConfusionMatrix::from_predictions(&labels, &predictions, classes).report(),
+                    );
+                }
+                Metric::RocAuc => report.roc_auc = curve(roc_auc),
+                Metric::AveragePrecision => report.average_precision = curve(average_precision),
+                Metric::Regression => {
+                    report.regression = Some(RegressionReport::new(outputs, targets))
+                }
+            }
+        }
+        report
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "samples: {}\nloss: {:.6}", self.samples, self.loss)?;
+        if let Some(accuracy) = self.accuracy {
+            write!(f, "\naccuracy: {accuracy:.4}")?;
+        }
+        for (k, accuracy) in &self.top_k_accuracy {
+            write!(f, "\ntop-{k} accuracy: {accuracy:.4}")?;
+        }
+        if let Some(roc_auc) = self.roc_auc {
+            write!(f, "\nROC AUC: {roc_auc:.4}")?;
+        }
+        if let Some(average_precision) = self.average_precision {
+            write!(f, "\naverage precision: {average_precision:.4}")?;
+        }
+        if let Some(classification) = &self.classification {
+            write!(f, "\n\n{classification}")?;
+        }
+        if let Some(regression) = &self.regression {
+            write!(f, "\n\n{regression}")?;
+        }
+        Ok(())
+    }
+}
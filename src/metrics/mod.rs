@@ -0,0 +1,16 @@
+//! Measures of how well a network's outputs match the targets.
+
+mod classification;
+mod curves;
+mod evaluation;
+mod regression;
+
+pub use classification::{
+    accuracy, top_k_accuracy, Average, ClassScores, ClassificationReport, ConfusionMatrix,
+};
+pub use curves::{average_precision, roc_auc, PrecisionRecallCurve, RocCurve, Threshold};
+pub use evaluation::{Metric, Report};
+pub use regression::{
+    explained_variance, mean_absolute_error, mean_absolute_percentage_error, mean_squared_error,
+    r2_score, root_mean_squared_error, RegressionReport, RegressionScores,
+};
@@ -0,0 +1,197 @@
+use std::fmt;
+
+/// Scores of one output, or their unweighted mean over all outputs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegressionScores {
+    /// Mean squared error
+    pub mse: f32,
+    /// Root mean squared error
+    pub rmse: f32,
+    /// Mean absolute error
+    pub mae: f32,
+    /// Mean absolute percentage error as a fraction, relative to the target
+    pub mape: f32,
+    /// Coefficient of determination
+    pub r2: f32,
+    /// Share of the target variance explained, which unlike R² ignores a
+    /// constant offset of the outputs
+    pub explained_variance: f32,
+}
+
+/// Regression scores of every output of a network and their average.
+///
+/// ```no_run
+/// use neural::{data::{synthetic, Dataset}, metrics::RegressionReport, nn::NeuralNetwork};
+///
+/// let test = synthetic::sine(200, 0.0, 1);
+/// let mut nn = NeuralNetwork::new(1, vec![16], 1);
+/// let outputs = (0..test.len())
+///     .map(|i| nn.feedforward(test.input(i).to_vec()))
+///     .collect::<Vec<_>>();
+/// let targets = (0..test.len()).map(|i| test.target(i)).collect::<Vec<_>>();
+/// println!("{}", RegressionReport::new(&outputs, &targets));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegressionReport {
+    pub outputs: Vec<RegressionScores>,
+    pub average: RegressionScores,
+}
+
+/// Share of the total variation explained, one minus the ratio of residual
+/// to total variation. `1.0` for a perfect fit of a constant target and `0.0`
+/// for an imperfect one.
+fn explained_fraction(residual: f64, total: f64) -> f32 {
+    if total == 0.0 {
+        if residual == 0.0 {
+            1.0
+        } else {
+            0.0
+        }
+    } else {
+        (1.0 - residual / total) as f32
+    }
+}
+
+impl RegressionScores {
+    fn new(outputs: &[f32], targets: &[f32]) -> Self {
+        let n = targets.len() as f64;
+        let errors = outputs
+            .iter()
+            .zip(targets)
+            .map(|(o, t)| (*t - *o) as f64)
+            .collect::<Vec<f64>>();
+
+        let mse = errors.iter().map(|e| e * e).sum::<f64>() / n;
+        let mae = errors.iter().map(|e| e.abs()).sum::<f64>() / n;
+        let mape = errors
+            .iter()
+            .zip(targets)
+            .map(|(e, t)| e.abs() / (t.abs() as f64).max(f32::EPSILON as f64))
+            .sum::<f64>()
+            / n;
+
+        let target_mean = targets.iter().map(|t| *t as f64).sum::<f64>() / n;
+        let target_variance = targets
+            .iter()
+            .map(|t| (*t as f64 - target_mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let error_mean = errors.iter().sum::<f64>() / n;
+        let error_variance = errors.iter().map(|e| (e - error_mean).powi(2)).sum::<f64>() / n;
+
+        Self {
+            mse: mse as f32,
+            rmse: mse.sqrt() as f32,
+            mae: mae as f32,
+            mape: mape as f32,
+            r2: explained_fraction(mse, target_variance),
+            explained_variance: explained_fraction(error_variance, target_variance),
+        }
+    }
+
+    fn mean(scores: &[Self]) -> Self {
+        let n = scores.len() as f32;
+        let mean = |f: fn(&Self) -> f32| scores.iter().map(f).sum::<f32>() / n;
+        Self {
+            mse: mean(|s| s.mse),
+            rmse: mean(|s| s.rmse),
+            mae: mean(|s| s.mae),
+            mape: mean(|s| s.mape),
+            r2: mean(|s| s.r2),
+            explained_variance: mean(|s| s.explained_variance),
+        }
+    }
+}
+
+/// Values of output `i` of every sample.
+fn column<S: AsRef<[f32]>>(samples: &[S], i: usize) -> Vec<f32> {
+    samples.iter().map(|s| s.as_ref()[i]).collect()
+}
+
+impl RegressionReport {
+    /// Scores `outputs` against `targets`, both given one sample at a time,
+    /// e.g. as returned by [`crate::nn::NeuralNetwork::feedforward`] and
+    /// [`crate::data::Dataset::target`].
+    pub fn new<O: AsRef<[f32]>, T: AsRef<[f32]>>(outputs: &[O], targets: &[T]) -> Self {
+        assert_eq!(outputs.len(), targets.len());
+        assert!(!targets.is_empty());
+        let len = targets[0].as_ref().len();
+        assert!(len > 0);
+        assert!(outputs.iter().all(|o| o.as_ref().len() == len));
+        assert!(targets.iter().all(|t| t.as_ref().len() == len));
+
+        let scores = (0..len)
+            .map(|i| RegressionScores::new(&column(outputs, i), &column(targets, i)))
+            .collect::<Vec<_>>();
+        Self {
+            average: RegressionScores::mean(&scores),
+            outputs: scores,
+        }
+    }
+}
+
+impl fmt::Display for RegressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = "average".len().max(self.outputs.len().to_string().len());
+        let row = |f: &mut fmt::Formatter<'_>, name: &str, s: &RegressionScores| {
+            write!(
+                f,
+                "{name:>width$} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                s.mse, s.rmse, s.mae, s.mape, s.r2, s.explained_variance
+            )
+        };
+
+        write!(
+            f,
+            "{:>width$} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "", "mse", "rmse", "mae", "mape", "r2", "explained"
+        )?;
+        for (i, scores) in self.outputs.iter().enumerate() {
+            writeln!(f)?;
+            row(f, &i.to_string(), scores)?;
+        }
+        if self.outputs.len() > 1 {
+            writeln!(f)?;
+            row(f, "average", &self.average)?;
+        }
+        Ok(())
+    }
+}
+
+/// Mean squared error averaged over the outputs.
+pub fn mean_squared_error<O: AsRef<[f32]>, T: AsRef<[f32]>>(outputs: &[O], targets: &[T]) -> f32 {
+    RegressionReport::new(outputs, targets).average.mse
+}
+
+/// Root mean squared error averaged over the outputs.
+pub fn root_mean_squared_error<O: AsRef<[f32]>, T: AsRef<[f32]>>(
+    outputs: &[O],
+    targets: &[T],
+) -> f32 {
+    RegressionReport::new(outputs, targets).average.rmse
+}
+
+/// Mean absolute error averaged over the outputs.
+pub fn mean_absolute_error<O: AsRef<[f32]>, T: AsRef<[f32]>>(outputs: &[O], targets: &[T]) -> f32 {
+    RegressionReport::new(outputs, targets).average.mae
+}
+
+/// Mean absolute percentage error averaged over the outputs.
+pub fn mean_absolute_percentage_error<O: AsRef<[f32]>, T: AsRef<[f32]>>(
+    outputs: &[O],
+    targets: &[T],
+) -> f32 {
+    RegressionReport::new(outputs, targets).average.mape
+}
+
+/// R² averaged over the outputs.
+pub fn r2_score<O: AsRef<[f32]>, T: AsRef<[f32]>>(outputs: &[O], targets: &[T]) -> f32 {
+    RegressionReport::new(outputs, targets).average.r2
+}
+
+/// Explained variance averaged over the outputs.
+pub fn explained_variance<O: AsRef<[f32]>, T: AsRef<[f32]>>(outputs: &[O], targets: &[T]) -> f32 {
+    RegressionReport::new(outputs, targets)
+        .average
+        .explained_variance
+}
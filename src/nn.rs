@@ -1,4 +1,16 @@
-use crate::matrix::Matrix;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    data::{DataLoader, Dataset},
+    format::binary,
+    labels::probabilities,
+    matrix::Matrix,
+    metrics::{Metric, Report},
+};
 
 // #[inline(always)]
 // fn sigmoid(x: f32) -> f32 {
@@ -11,23 +23,164 @@ use crate::matrix::Matrix;
 //     y * (1.0 - y)
 // }
 
+/// Activation function applied to the output of a [`Layer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Activation {
+    Sigmoid,
+    Relu,
+    Tanh,
+    /// Softmax over the outputs of the layer
+    Softmax,
+    /// No activation, the layer is purely linear
+    Identity,
+}
+
+impl Activation {
+    /// Lowercase name used by the export formats.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activation::Sigmoid => "sigmoid",
+            Activation::Relu => "relu",
+            Activation::Tanh => "tanh",
+            Activation::Softmax => "softmax",
+            Activation::Identity => "identity",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sigmoid" => Some(Activation::Sigmoid),
+            "relu" => Some(Activation::Relu),
+            "tanh" => Some(Activation::Tanh),
+            "softmax" => Some(Activation::Softmax),
+            "identity" => Some(Activation::Identity),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, m: &mut Matrix) {
+        match self {
+            Activation::Sigmoid => m.sigmoid(),
+            Activation::Relu => m.relu(),
+            Activation::Tanh => m.tanh(),
+            Activation::Softmax => m.softmax(),
+            Activation::Identity => {}
+        }
+    }
+
+    /// Like [`Self::apply`] for a matrix with one sample per column.
+    fn apply_columns(&self, m: &mut Matrix) {
+        match self {
+            // The softmax kernel normalizes over the whole matrix
+            Activation::Softmax => {
+                let (rows, columns) = m.size();
+                let outputs = m
+                    .to_vec()
+                    .chunks_exact(rows)
+                    .flat_map(probabilities)
+                    .collect::<Vec<f32>>();
+                *m = Matrix::from_slice_cm(&outputs, rows, columns);
+            }
+            _ => self.apply(m),
+        }
+    }
+
+    /// Backpropagates `errors` through the activation into `res`, given the
+    /// activated outputs `y`.
+    fn backward(&self, y: &mut Matrix, errors: &Matrix, res: &mut Matrix) {
+        match self {
+            Activation::Sigmoid => {
+                y.dsigmoid(res);
+                res.multiply_matrix(errors);
+            }
+            Activation::Relu => {
+                y.drelu(res);
+                res.multiply_matrix(errors);
+            }
+            Activation::Tanh => {
+                y.dtanh(res);
+                res.multiply_matrix(errors);
+            }
+            Activation::Softmax => y.dsoftmax(errors, res),
+            Activation::Identity => {
+                res.zero();
+                res.add_matrix(errors);
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Layer {
     pub weights: Matrix,
     pub bias: Matrix,
+    pub activation: Activation,
     // Reusable buffer
     pub gradients: Matrix,
     pub transposed: Matrix,
     pub weights_t: Matrix,
     pub weights_deltas: Matrix,
+    // Gradients summed up since the last `zero_grad`
+    pub weights_grad: Matrix,
+    pub bias_grad: Matrix,
 }
 
+impl Layer {
+    /// Creates a layer from a `(outputs, inputs)` weight matrix and a
+    /// `(outputs, 1)` bias vector.
+    pub fn new(weights: Matrix, bias: Matrix, activation: Activation) -> Self {
+        let (n_output, n_input) = weights.size();
+        assert_eq!(bias.size(), (n_output, 1));
+
+        Self {
+            weights,
+            bias,
+            activation,
+            gradients: Matrix::new(n_output, 1),
+            transposed: Matrix::new(1, n_input),
+            weights_t: Matrix::new(n_input, n_output),
+            weights_deltas: Matrix::new(n_output, n_input),
+            weights_grad: Matrix::new(n_output, n_input),
+            bias_grad: Matrix::new(n_output, 1),
+        }
+    }
+
+    pub fn n_input(&self) -> usize {
+        self.weights.size().1
+    }
+
+    pub fn n_output(&self) -> usize {
+        self.weights.size().0
+    }
+}
+
+/// Weight and bias gradients of a single layer.
+#[derive(Debug, Clone)]
+pub struct LayerGradients {
+    pub weights: Matrix,
+    pub bias: Matrix,
+}
+
+/// Per-layer gradients as returned by [`NeuralNetwork::backward`], ordered
+/// from the input layer to the output layer.
+#[derive(Debug, Clone)]
+pub struct Gradients {
+    pub layers: Vec<LayerGradients>,
+}
+
+/// Number of samples run through the network at once by
+/// [`NeuralNetwork::evaluate`].
+const EVALUATION_BATCH_SIZE: usize = 256;
+
 #[derive(Debug)]
 pub struct NeuralNetwork {
     layers: Vec<Layer>,
     // Reusable buffers for the feed forward step
     results: Vec<Matrix>,
     learning_rate: f32,
+    // Number of samples summed into the layers' gradient accumulators
+    accumulated: usize,
 }
 
 impl NeuralNetwork {
@@ -47,47 +200,132 @@ impl NeuralNetwork {
             let mut bias = Matrix::new(neuron_count, 1);
             bias.randomize();
 
-            layers.push(Layer {
-                weights,
-                bias,
-                gradients: Matrix::new(1, 1),
-                transposed: Matrix::new(1, 1),
-                weights_t: Matrix::new(input_weights_count, neuron_count),
-                weights_deltas: Matrix::new(1, 1),
-            });
+            layers.push(Layer::new(weights, bias, Activation::Sigmoid));
             input_weights_count = neuron_count;
         }
 
-        let mut results = Vec::new();
-        let mut inputs = (n_input, 1);
-        for layer in layers.iter_mut() {
-            let size = (layer.weights.size().0, inputs.1);
-            layer.gradients = Matrix::new(size.0, size.1);
-            layer.transposed = Matrix::new(inputs.1, inputs.0);
-            layer.weights_deltas = Matrix::new(layer.gradients.size().0, layer.transposed.size().1);
-            results.push(Matrix::new(size.0, size.1));
-            inputs = size;
+        Self::from_layers(layers)
+    }
+
+    /// Builds a network from already initialized layers, ordered from the
+    /// input layer to the output layer.
+    pub fn from_layers(layers: Vec<Layer>) -> Self {
+        assert!(!layers.is_empty());
+        for pair in layers.windows(2) {
+            assert_eq!(pair[0].n_output(), pair[1].n_input());
         }
 
+        let results = layers
+            .iter()
+            .map(|layer| Matrix::new(layer.n_output(), 1))
+            .collect();
+
         Self {
             learning_rate: 0.003,
             results,
             layers,
+            accumulated: 0,
         }
     }
 
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub(crate) fn layers_mut(&mut self) -> &mut [Layer] {
+        &mut self.layers
+    }
+
+    pub fn n_input(&self) -> usize {
+        self.layers[0].n_input()
+    }
+
+    pub fn n_output(&self) -> usize {
+        self.layers[self.layers.len() - 1].n_output()
+    }
+
+    pub fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
     pub fn set_learning_rate(&mut self, lr: f32) {
         self.learning_rate = lr;
     }
 
-    pub fn feedforward(&mut self, input: Vec<f32>) -> Vec<f32> {
-        let inputs = Matrix::from_slice(&input);
+    /// Writes the network to `path` in the format described in
+    /// [`crate::format::binary`].
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), binary::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        binary::write(self, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a network written by [`Self::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, binary::Error> {
+        binary::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Exports the network as an ONNX model, see [`crate::format::onnx`].
+    pub fn save_onnx<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        crate::format::onnx::write(self, &mut writer)?;
+        writer.flush()
+    }
+
+    /// Builds a network from an ONNX model made of dense layers, see
+    /// [`crate::format::onnx`] for what is supported.
+    pub fn load_onnx<P: AsRef<Path>>(path: P) -> Result<Self, crate::format::onnx::Error> {
+        crate::format::onnx::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Dumps the weights and biases into a `.npz` archive as
+    /// `layers.{i}.weight` and `layers.{i}.bias`.
+    #[cfg(feature = "npz")]
+    pub fn save_npz<P: AsRef<Path>>(&self, path: P) -> Result<(), crate::format::npy::Error> {
+        let names = (0..self.layers.len())
+            .flat_map(|i| [format!("layers.{i}.weight"), format!("layers.{i}.bias")])
+            .collect::<Vec<String>>();
+        let matrices = self
+            .layers
+            .iter()
+            .flat_map(|layer| [&layer.weights, &layer.bias])
+            .collect::<Vec<&Matrix>>();
+        let named = names
+            .iter()
+            .map(String::as_str)
+            .zip(matrices)
+            .collect::<Vec<(&str, &Matrix)>>();
+
+        crate::format::npy::write_npz(&named, BufWriter::new(File::create(path)?))
+    }
 
+    /// Exports the weights and biases to a safetensors file, see
+    /// [`crate::format::safetensors`] for the tensor names.
+    #[cfg(feature = "safetensors")]
+    pub fn save_safetensors<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<(), crate::format::safetensors::Error> {
+        std::fs::write(path, crate::format::safetensors::to_bytes(self)?)?;
+        Ok(())
+    }
+
+    /// Builds a network from a safetensors file with the layout written by
+    /// [`Self::save_safetensors`].
+    #[cfg(feature = "safetensors")]
+    pub fn load_safetensors<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<Self, crate::format::safetensors::Error> {
+        crate::format::safetensors::from_bytes(&std::fs::read(path)?)
+    }
+
+    fn forward(&mut self, inputs: &Matrix) {
         self.layers[0]
             .weights
-            .product_into(&inputs, &mut self.results[0]);
+            .product_into(inputs, &mut self.results[0]);
         self.results[0].add_matrix(&self.layers[0].bias);
-        self.results[0].sigmoid();
+        self.layers[0].activation.apply(&mut self.results[0]);
 
         for (index, layer) in self.layers.iter().enumerate().skip(1) {
             layer.weights.product_into(
@@ -95,67 +333,221 @@ impl NeuralNetwork {
                 &mut self.results[index],
             );
             self.results[index].add_matrix(&layer.bias);
-            self.results[index].sigmoid();
+            layer.activation.apply(&mut self.results[index]);
         }
+    }
 
+    pub fn feedforward(&mut self, input: Vec<f32>) -> Vec<f32> {
+        let inputs = Matrix::from_slice(&input);
+        self.forward(&inputs);
         self.results.last().unwrap().to_vec()
     }
 
-    pub fn train(&mut self, inputs: &[f32], targets: &[f32]) {
-        let inputs = Matrix::from_slice(inputs);
-        let orig_inputs = inputs.clone();
+    /// Outputs for every column of `inputs`, e.g. a [`crate::data::Batch`],
+    /// in one pass per layer. Leaves the buffers used for training alone.
+    pub fn predict_batch(&self, inputs: &Matrix) -> Matrix {
+        assert_eq!(inputs.size().0, self.n_input());
+        let columns = inputs.size().1;
+        let ones = Matrix::from_slice_cm(&vec![1.0; columns], 1, columns);
 
-        self.layers[0]
-            .weights
-            .product_into(&inputs, &mut self.results[0]);
-        self.results[0].add_matrix(&self.layers[0].bias);
-        self.results[0].sigmoid();
+        let mut outputs: Option<Matrix> = None;
+        for layer in &self.layers {
+            let mut results = layer.weights.product(outputs.as_ref().unwrap_or(inputs));
+            results.add_matrix(&layer.bias.product(&ones));
+            layer.activation.apply_columns(&mut results);
+            outputs = Some(results);
+        }
+        outputs.unwrap()
+    }
 
-        for (index, layer) in self.layers.iter().enumerate().skip(1) {
-            layer.weights.product_into(
-                unsafe { &*self.results.as_ptr().offset(index as isize - 1) },
-                &mut self.results[index],
+    /// Runs the network on every sample of `dataset` in batches and computes
+    /// the loss and `metrics`.
+    ///
+    /// No layer behaves differently during training, so evaluating only
+    /// needs the weights and never changes the network.
+    ///
+    /// ```no_run
+    /// use neural::{
+    ///     data::mnist::{MnistLoader, Split},
+    ///     metrics::Metric,
+    ///     nn::NeuralNetwork,
+    /// };
+    ///
+    /// let tests = MnistLoader::new("./data/mnist").load(Split::Test).unwrap();
+    /// let nn = NeuralNetwork::load("mnist.bin").unwrap();
+    /// let report = nn.evaluate(&tests, &[Metric::Accuracy, Metric::TopKAccuracy(3)]);
+    /// println!("{report}");
+    /// ```
+    pub fn evaluate<D: Dataset + ?Sized>(&self, dataset: &D, metrics: &[Metric]) -> Report {
+        assert_eq!(dataset.input_len(), self.n_input());
+        assert_eq!(dataset.target_len(), self.n_output());
+
+        let mut outputs = Vec::with_capacity(dataset.len());
+        let mut loader = DataLoader::new(dataset, EVALUATION_BATCH_SIZE);
+        for batch in loader.next_epoch() {
+            let predictions = self.predict_batch(&batch.inputs).to_vec();
+            outputs.extend(
+                predictions
+                    .chunks_exact(self.n_output())
+                    .map(|output| output.to_vec()),
             );
-            self.results[index].add_matrix(&layer.bias);
-            self.results[index].sigmoid();
         }
+        let targets = (0..dataset.len())
+            .map(|index| dataset.target(index))
+            .collect::<Vec<_>>();
+        Report::new(&outputs, &targets, metrics)
+    }
+
+    /// Runs a forward pass and leaves the gradients of the squared error loss
+    /// in every layer's `weights_deltas` (weights) and `gradients` (bias) buffers.
+    fn compute_gradients(&mut self, inputs: &[f32], targets: &[f32]) {
+        let inputs = Matrix::from_slice(inputs);
+        self.forward(&inputs);
 
         let targets = Matrix::from_slice(targets);
         let outputs = &self.results[self.results.len() - 1];
-        let mut errors = targets.subtract_matrix(outputs);
-
-        // Skip first element so we can ommit branching
-        for (index, layer) in self.layers.iter_mut().enumerate().skip(1).rev() {
-            self.results[index].dsigmoid(&mut layer.gradients);
-            layer.gradients.multiply_matrix(&errors);
-            layer.gradients.multiply_scalar(self.learning_rate);
+        // dL/dy for L = 0.5 * |y - t|^2
+        let mut errors = outputs.subtract_matrix(&targets);
 
-            self.results[index - 1].transpose_into(&mut layer.transposed);
+        for (index, layer) in self.layers.iter_mut().enumerate().rev() {
+            layer
+                .activation
+                .backward(&mut self.results[index], &errors, &mut layer.gradients);
 
+            let layer_inputs = if index == 0 {
+                &inputs
+            } else {
+                &self.results[index - 1]
+            };
+            layer_inputs.transpose_into(&mut layer.transposed);
             layer
                 .gradients
                 .product_into(&layer.transposed, &mut layer.weights_deltas);
 
-            layer.weights.add_matrix(&layer.weights_deltas);
-            layer.bias.add_matrix(&layer.gradients);
+            if index > 0 {
+                layer.weights.transpose_into(&mut layer.weights_t);
+                errors = layer.weights_t.product(&layer.gradients);
+            }
+        }
+    }
+
+    /// Computes the weight and bias gradients for a single sample without
+    /// touching the network parameters.
+    pub fn backward(&mut self, inputs: &[f32], targets: &[f32]) -> Gradients {
+        self.compute_gradients(inputs, targets);
 
-            layer.weights.transpose_into(&mut layer.weights_t);
-            errors = layer.weights_t.product(&errors);
+        Gradients {
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| LayerGradients {
+                    weights: layer.weights_deltas.clone(),
+                    bias: layer.gradients.clone(),
+                })
+                .collect(),
         }
+    }
 
-        let layer = &mut self.layers[0];
-        self.results[0].dsigmoid(&mut layer.gradients);
-        layer.gradients.multiply_matrix(&errors);
-        layer.gradients.multiply_scalar(self.learning_rate);
+    /// Takes a gradient descent step of size `learning_rate` along `gradients`.
+    pub fn apply_gradients(&mut self, gradients: &Gradients) {
+        assert_eq!(gradients.layers.len(), self.layers.len());
 
-        orig_inputs.transpose_into(&mut layer.transposed);
-        layer
-            .gradients
-            .product_into(&layer.transposed, &mut layer.weights_deltas);
+        for (layer, grads) in self.layers.iter_mut().zip(&gradients.layers) {
+            layer
+                .weights
+                .add_scaled_matrix(&grads.weights, -self.learning_rate);
+            layer
+                .bias
+                .add_scaled_matrix(&grads.bias, -self.learning_rate);
+        }
+    }
+
+    /// Clears the gradient accumulators filled by [`Self::accumulate`].
+    pub fn zero_grad(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.weights_grad.zero();
+            layer.bias_grad.zero();
+        }
+        self.accumulated = 0;
+    }
+
+    /// Adds the gradients of a single sample to the accumulators without
+    /// updating the parameters.
+    pub fn accumulate(&mut self, inputs: &[f32], targets: &[f32]) {
+        self.compute_gradients(inputs, targets);
+
+        for layer in self.layers.iter_mut() {
+            layer.weights_grad.add_matrix(&layer.weights_deltas);
+            layer.bias_grad.add_matrix(&layer.gradients);
+        }
+        self.accumulated += 1;
+    }
+
+    /// Adds externally computed gradients, e.g. from [`Self::backward`] on
+    /// another worker, counting them as one sample.
+    pub fn accumulate_gradients(&mut self, gradients: &Gradients) {
+        assert_eq!(gradients.layers.len(), self.layers.len());
+
+        for (layer, grads) in self.layers.iter_mut().zip(&gradients.layers) {
+            layer.weights_grad.add_matrix(&grads.weights);
+            layer.bias_grad.add_matrix(&grads.bias);
+        }
+        self.accumulated += 1;
+    }
+
+    /// Number of samples accumulated since the last [`Self::zero_grad`].
+    pub fn accumulated(&self) -> usize {
+        self.accumulated
+    }
 
-        layer.weights.add_matrix(&layer.weights_deltas);
-        layer.bias.add_matrix(&layer.gradients);
+    pub(crate) fn set_accumulated(&mut self, accumulated: usize) {
+        self.accumulated = accumulated;
+    }
+
+    /// Applies the average of the accumulated gradients and clears the
+    /// accumulators. Does nothing if nothing was accumulated.
+    pub fn step(&mut self) {
+        if self.accumulated == 0 {
+            return;
+        }
+
+        let scale = -self.learning_rate / self.accumulated as f32;
+        for layer in self.layers.iter_mut() {
+            layer.weights.add_scaled_matrix(&layer.weights_grad, scale);
+            layer.bias.add_scaled_matrix(&layer.bias_grad, scale);
+        }
+        self.zero_grad();
+    }
 
-        layer.weights.transpose_into(&mut layer.weights_t);
+    /// Trains on every column of `inputs` and `targets`, e.g. a
+    /// [`crate::data::Batch`], taking a single step with the averaged
+    /// gradients. Gradients accumulated before are part of the average.
+    pub fn train_batch(&mut self, inputs: &Matrix, targets: &Matrix) {
+        assert_eq!(inputs.size().0, self.n_input());
+        assert_eq!(targets.size().0, self.n_output());
+        assert_eq!(inputs.size().1, targets.size().1);
+
+        let inputs = inputs.to_vec();
+        let targets = targets.to_vec();
+        for (inputs, targets) in inputs
+            .chunks_exact(self.n_input())
+            .zip(targets.chunks_exact(self.n_output()))
+        {
+            self.accumulate(inputs, targets);
+        }
+        self.step();
+    }
+
+    pub fn train(&mut self, inputs: &[f32], targets: &[f32]) {
+        self.compute_gradients(inputs, targets);
+
+        for layer in self.layers.iter_mut() {
+            layer
+                .weights
+                .add_scaled_matrix(&layer.weights_deltas, -self.learning_rate);
+            layer
+                .bias
+                .add_scaled_matrix(&layer.gradients, -self.learning_rate);
+        }
     }
 }
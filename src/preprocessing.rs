@@ -0,0 +1,671 @@
+//! Feature scaling fitted on training data.
+//!
+//! Data is laid out like the batches of [`crate::data::DataLoader`]: one
+//! sample per matrix column, one feature per row. Scalers are fitted once on
+//! the training set and then applied unchanged to validation, test and
+//! inference inputs. A [`Pipeline`] stores them together with the network so
+//! a loaded model preprocesses its inputs exactly like during training.
+//!
+//! The pipeline file format, with all integers and floats little-endian:
+//!
+//! ```text
+//! magic          8 bytes  "NEURALPP"
+//! version        u32      currently 1
+//! input_scaler   scaler
+//! output_scaler  scaler
+//! network_len    u64
+//! network        network_len bytes, see crate::format::binary
+//! checksum       u32      CRC-32 of every preceding byte
+//!
+//! scaler:
+//!     kind       u8       0 none, 1 standard, 2 min-max, 3 robust, 4 normalizer
+//!     standard:   features u32, mean f32 * features, std f32 * features
+//!     min-max:    low f32, high f32, features u32, min f32 * features,
+//!                 max f32 * features
+//!     robust:     lower f32, upper f32, features u32, median f32 * features,
+//!                 iqr f32 * features
+//!     normalizer: norm u8 (0 L1, 1 L2, 2 max)
+//! ```
+
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    format::{binary, crc32, Cursor, Framing},
+    matrix::Matrix,
+    nn::NeuralNetwork,
+};
+
+pub const MAGIC: &[u8; 8] = b"NEURALPP";
+pub const VERSION: u32 = 1;
+
+/// A preprocessing step applied to every sample separately.
+pub trait Transformer {
+    /// Learns the parameters of the transformation from `data`.
+    fn fit(&mut self, data: &Matrix);
+
+    fn transform_sample(&self, sample: &mut [f32]);
+
+    fn inverse_transform_sample(&self, sample: &mut [f32]);
+
+    fn transform(&self, data: &Matrix) -> Matrix {
+        let (rows, columns) = data.size();
+        let mut values = data.to_vec();
+        for sample in values.chunks_exact_mut(rows) {
+            self.transform_sample(sample);
+        }
+        Matrix::from_slice_cm(&values, rows, columns)
+    }
+
+    fn inverse_transform(&self, data: &Matrix) -> Matrix {
+        let (rows, columns) = data.size();
+        let mut values = data.to_vec();
+        for sample in values.chunks_exact_mut(rows) {
+            self.inverse_transform_sample(sample);
+        }
+        Matrix::from_slice_cm(&values, rows, columns)
+    }
+
+    fn fit_transform(&mut self, data: &Matrix) -> Matrix {
+        self.fit(data);
+        self.transform(data)
+    }
+}
+
+/// Values of every feature across the samples of `data`.
+fn features(data: &Matrix) -> Vec<Vec<f32>> {
+    let (rows, columns) = data.size();
+    assert!(columns > 0, "cannot fit on zero samples");
+    let values = data.to_vec();
+    (0..rows)
+        .map(|row| (0..columns).map(|c| values[c * rows + row]).collect())
+        .collect()
+}
+
+/// Replaces a zero scale of a constant feature so it passes through
+/// unscaled instead of dividing by zero.
+fn nonzero(scale: f32) -> f32 {
+    if scale == 0.0 {
+        1.0
+    } else {
+        scale
+    }
+}
+
+/// Scales every feature to zero mean and unit variance.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StandardScaler {
+    pub mean: Vec<f32>,
+    /// Population standard deviation
+    pub std: Vec<f32>,
+}
+
+impl StandardScaler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Transformer for StandardScaler {
+    fn fit(&mut self, data: &Matrix) {
+        self.mean.clear();
+        self.std.clear();
+        for feature in features(data) {
+            let n = feature.len() as f64;
+            let mean = feature.iter().map(|v| *v as f64).sum::<f64>() / n;
+            let var = feature
+                .iter()
+                .map(|v| (*v as f64 - mean).powi(2))
+                .sum::<f64>()
+                / n;
+            self.mean.push(mean as f32);
+            self.std.push(var.sqrt() as f32);
+        }
+    }
+
+    fn transform_sample(&self, sample: &mut [f32]) {
+        assert_eq!(sample.len(), self.mean.len());
+        for ((v, mean), std) in sample.iter_mut().zip(&self.mean).zip(&self.std) {
+            *v = (*v - mean) / nonzero(*std);
+        }
+    }
+
+    fn inverse_transform_sample(&self, sample: &mut [f32]) {
+        assert_eq!(sample.len(), self.mean.len());
+        for ((v, mean), std) in sample.iter_mut().zip(&self.mean).zip(&self.std) {
+            *v = *v * nonzero(*std) + mean;
+        }
+    }
+}
+
+/// Scales every feature linearly so its training range maps onto
+/// `low..=high`, `0..=1` by default.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MinMaxScaler {
+    pub low: f32,
+    pub high: f32,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+impl Default for MinMaxScaler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MinMaxScaler {
+    pub fn new() -> Self {
+        Self {
+            low: 0.0,
+            high: 1.0,
+            min: Vec::new(),
+            max: Vec::new(),
+        }
+    }
+
+    pub fn range(mut self, low: f32, high: f32) -> Self {
+        assert!(low < high);
+        self.low = low;
+        self.high = high;
+        self
+    }
+}
+
+impl Transformer for MinMaxScaler {
+    fn fit(&mut self, data: &Matrix) {
+        self.min.clear();
+        self.max.clear();
+        for feature in features(data) {
+            self.min
+                .push(feature.iter().copied().fold(f32::INFINITY, f32::min));
+            self.max
+                .push(feature.iter().copied().fold(f32::NEG_INFINITY, f32::max));
+        }
+    }
+
+    fn transform_sample(&self, sample: &mut [f32]) {
+        assert_eq!(sample.len(), self.min.len());
+        for ((v, min), max) in sample.iter_mut().zip(&self.min).zip(&self.max) {
+            *v = self.low + (*v - min) / nonzero(max - min) * (self.high - self.low);
+        }
+    }
+
+    fn inverse_transform_sample(&self, sample: &mut [f32]) {
+        assert_eq!(sample.len(), self.min.len());
+        for ((v, min), max) in sample.iter_mut().zip(&self.min).zip(&self.max) {
+            *v = (*v - self.low) / (self.high - self.low) * nonzero(max - min) + min;
+        }
+    }
+}
+
+/// Centers every feature on its median and scales it by its interquartile
+/// range, which keeps outliers from dominating the scale.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RobustScaler {
+    /// Lower and upper percentile of the range, `25` and `75` by default
+    pub lower: f32,
+    pub upper: f32,
+    pub median: Vec<f32>,
+    pub iqr: Vec<f32>,
+}
+
+impl Default for RobustScaler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RobustScaler {
+    pub fn new() -> Self {
+        Self {
+            lower: 25.0,
+            upper: 75.0,
+            median: Vec::new(),
+            iqr: Vec::new(),
+        }
+    }
+
+    pub fn quantile_range(mut self, lower: f32, upper: f32) -> Self {
+        assert!((0.0..upper).contains(&lower) && upper <= 100.0);
+        self.lower = lower;
+        self.upper = upper;
+        self
+    }
+}
+
+/// Percentile of sorted values with linear interpolation between neighbours.
+fn percentile(sorted: &[f32], percent: f32) -> f32 {
+    let position = percent / 100.0 * (sorted.len() - 1) as f32;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f32)
+}
+
+impl Transformer for RobustScaler {
+    fn fit(&mut self, data: &Matrix) {
+        self.median.clear();
+        self.iqr.clear();
+        for mut feature in features(data) {
+            feature.sort_by(f32::total_cmp);
+            self.median.push(percentile(&feature, 50.0));
+            self.iqr
+                .push(percentile(&feature, self.upper) - percentile(&feature, self.lower));
+        }
+    }
+
+    fn transform_sample(&self, sample: &mut [f32]) {
+        assert_eq!(sample.len(), self.median.len());
+        for ((v, median), iqr) in sample.iter_mut().zip(&self.median).zip(&self.iqr) {
+            *v = (*v - median) / nonzero(*iqr);
+        }
+    }
+
+    fn inverse_transform_sample(&self, sample: &mut [f32]) {
+        assert_eq!(sample.len(), self.median.len());
+        for ((v, median), iqr) in sample.iter_mut().zip(&self.median).zip(&self.iqr) {
+            *v = *v * nonzero(*iqr) + median;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Norm {
+    L1,
+    L2,
+    Max,
+}
+
+/// Scales every sample to unit norm.
+///
+/// Unlike the other scalers this works on samples instead of features and
+/// learns nothing when fitted. The original norms are not kept, so
+/// [`Transformer::inverse_transform`] returns the unit-norm samples as they
+/// are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Normalizer {
+    pub norm: Norm,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self::new(Norm::L2)
+    }
+}
+
+impl Normalizer {
+    pub fn new(norm: Norm) -> Self {
+        Self { norm }
+    }
+}
+
+impl Transformer for Normalizer {
+    fn fit(&mut self, _data: &Matrix) {}
+
+    fn transform_sample(&self, sample: &mut [f32]) {
+        let norm = match self.norm {
+            Norm::L1 => sample.iter().map(|v| v.abs()).sum::<f32>(),
+            Norm::L2 => sample.iter().map(|v| v * v).sum::<f32>().sqrt(),
+            Norm::Max => sample.iter().fold(0.0, |m, v| v.abs().max(m)),
+        };
+        let norm = nonzero(norm);
+        for v in sample {
+            *v /= norm;
+        }
+    }
+
+    fn inverse_transform_sample(&self, _sample: &mut [f32]) {}
+}
+
+/// Any of the scalers, for storing a choice made at runtime.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Scaler {
+    Standard(StandardScaler),
+    MinMax(MinMaxScaler),
+    Robust(RobustScaler),
+    Normalizer(Normalizer),
+}
+
+impl Scaler {
+    fn inner(&self) -> &dyn Transformer {
+        match self {
+            Scaler::Standard(s) => s,
+            Scaler::MinMax(s) => s,
+            Scaler::Robust(s) => s,
+            Scaler::Normalizer(s) => s,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn Transformer {
+        match self {
+            Scaler::Standard(s) => s,
+            Scaler::MinMax(s) => s,
+            Scaler::Robust(s) => s,
+            Scaler::Normalizer(s) => s,
+        }
+    }
+}
+
+impl Transformer for Scaler {
+    fn fit(&mut self, data: &Matrix) {
+        self.inner_mut().fit(data)
+    }
+
+    fn transform_sample(&self, sample: &mut [f32]) {
+        self.inner().transform_sample(sample)
+    }
+
+    fn inverse_transform_sample(&self, sample: &mut [f32]) {
+        self.inner().inverse_transform_sample(sample)
+    }
+}
+
+impl From<StandardScaler> for Scaler {
+    fn from(s: StandardScaler) -> Self {
+        Scaler::Standard(s)
+    }
+}
+
+impl From<MinMaxScaler> for Scaler {
+    fn from(s: MinMaxScaler) -> Self {
+        Scaler::MinMax(s)
+    }
+}
+
+impl From<RobustScaler> for Scaler {
+    fn from(s: RobustScaler) -> Self {
+        Scaler::Robust(s)
+    }
+}
+
+impl From<Normalizer> for Scaler {
+    fn from(s: Normalizer) -> Self {
+        Scaler::Normalizer(s)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    /// The input ended before the announced amount of data was read.
+    Truncated,
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// Bytes follow the checksum.
+    TrailingData(usize),
+    UnknownScaler(u8),
+    UnknownNorm(u8),
+    Network(binary::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::BadMagic => write!(f, "not a pipeline file (bad magic number)"),
+            Error::UnsupportedVersion(v) => {
+                write!(f, "unsupported pipeline version {v} (expected {VERSION})")
+            }
+            Error::Truncated => write!(f, "pipeline is truncated"),
+            Error::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch: expected {expected:#010x}, found {found:#010x}"
+            ),
+            Error::TrailingData(n) => write!(f, "{n} unexpected bytes after the checksum"),
+            Error::UnknownScaler(kind) => write!(f, "unknown scaler kind {kind}"),
+            Error::UnknownNorm(norm) => write!(f, "unknown norm {norm}"),
+            Error::Network(e) => write!(f, "invalid network: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<Framing> for Error {
+    fn from(e: Framing) -> Self {
+        match e {
+            Framing::BadMagic => Error::BadMagic,
+            Framing::UnsupportedVersion(v) => Error::UnsupportedVersion(v),
+            Framing::Truncated => Error::Truncated,
+            Framing::ChecksumMismatch { expected, found } => {
+                Error::ChecksumMismatch { expected, found }
+            }
+            Framing::TrailingData(n) => Error::TrailingData(n),
+        }
+    }
+}
+
+impl From<binary::Error> for Error {
+    fn from(e: binary::Error) -> Self {
+        Error::Network(e)
+    }
+}
+
+/// A network with the scaling of its inputs and, for regression, of its
+/// targets.
+///
+/// ```no_run
+/// use neural::{
+///     matrix::{init, Matrix},
+///     nn::NeuralNetwork,
+///     preprocessing::{Pipeline, StandardScaler, Transformer},
+/// };
+///
+/// init();
+/// let inputs = Matrix::from_slice_cm(&[1.0, 200.0, 3.0, 400.0], 2, 2);
+/// let mut scaler = StandardScaler::new();
+/// let scaled = scaler.fit_transform(&inputs);
+/// // ... train on `scaled` ...
+/// let mut pipeline = Pipeline::new(NeuralNetwork::new(2, vec![4], 1)).input_scaler(scaler);
+/// pipeline.save("model.nnp").unwrap();
+///
+/// let mut pipeline = Pipeline::load("model.nnp").unwrap();
+/// let prediction = pipeline.predict(&[2.0, 300.0]);
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pipeline {
+    pub input_scaler: Option<Scaler>,
+    pub network: NeuralNetwork,
+    /// Scaling of the training targets, undone on the network outputs
+    pub output_scaler: Option<Scaler>,
+}
+
+impl Pipeline {
+    pub fn new(network: NeuralNetwork) -> Self {
+        Self {
+            input_scaler: None,
+            network,
+            output_scaler: None,
+        }
+    }
+
+    pub fn input_scaler<S: Into<Scaler>>(mut self, scaler: S) -> Self {
+        self.input_scaler = Some(scaler.into());
+        self
+    }
+
+    pub fn output_scaler<S: Into<Scaler>>(mut self, scaler: S) -> Self {
+        self.output_scaler = Some(scaler.into());
+        self
+    }
+
+    /// Scales the input, runs the network and unscales its output.
+    pub fn predict(&mut self, input: &[f32]) -> Vec<f32> {
+        let mut input = input.to_vec();
+        if let Some(scaler) = &self.input_scaler {
+            scaler.transform_sample(&mut input);
+        }
+        let mut output = self.network.feedforward(input);
+        if let Some(scaler) = &self.output_scaler {
+            scaler.inverse_transform_sample(&mut output);
+        }
+        output
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+        write_scaler(&mut buf, self.input_scaler.as_ref());
+        write_scaler(&mut buf, self.output_scaler.as_ref());
+
+        let mut network = Vec::new();
+        binary::write(&self.network, &mut network)?;
+        buf.extend_from_slice(&(network.len() as u64).to_le_bytes());
+        buf.extend_from_slice(&network);
+
+        let checksum = crc32(&buf);
+        buf.extend_from_slice(&checksum.to_le_bytes());
+        Ok(buf)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut cursor = Cursor::open(buf, MAGIC, VERSION)?;
+        let input_scaler = read_scaler(&mut cursor)?;
+        let output_scaler = read_scaler(&mut cursor)?;
+        let network_len = cursor.u64()? as usize;
+        let network = binary::read(&mut cursor.take(network_len)?)?;
+
+        cursor.finish()?;
+
+        Ok(Self {
+            input_scaler,
+            network,
+            output_scaler,
+        })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(&self.to_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Self::from_bytes(&buf)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+fn write_f32s(buf: &mut Vec<u8>, values: &[f32]) {
+    buf.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for v in values {
+        buf.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+fn write_scaler(buf: &mut Vec<u8>, scaler: Option<&Scaler>) {
+    match scaler {
+        None => buf.push(0),
+        Some(Scaler::Standard(s)) => {
+            buf.push(1);
+            write_f32s(buf, &s.mean);
+            for v in &s.std {
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        Some(Scaler::MinMax(s)) => {
+            buf.push(2);
+            buf.extend_from_slice(&s.low.to_le_bytes());
+            buf.extend_from_slice(&s.high.to_le_bytes());
+            write_f32s(buf, &s.min);
+            for v in &s.max {
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        Some(Scaler::Robust(s)) => {
+            buf.push(3);
+            buf.extend_from_slice(&s.lower.to_le_bytes());
+            buf.extend_from_slice(&s.upper.to_le_bytes());
+            write_f32s(buf, &s.median);
+            for v in &s.iqr {
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        Some(Scaler::Normalizer(s)) => {
+            buf.push(4);
+            buf.push(match s.norm {
+                Norm::L1 => 0,
+                Norm::L2 => 1,
+                Norm::Max => 2,
+            });
+        }
+    }
+}
+
+fn read_scaler(cursor: &mut Cursor<'_>) -> Result<Option<Scaler>, Error> {
+    Ok(Some(match cursor.u8()? {
+        0 => return Ok(None),
+        1 => {
+            let n = cursor.u32()? as usize;
+            Scaler::Standard(StandardScaler {
+                mean: cursor.f32s(n)?,
+                std: cursor.f32s(n)?,
+            })
+        }
+        2 => {
+            let (low, high) = (cursor.f32()?, cursor.f32()?);
+            let n = cursor.u32()? as usize;
+            Scaler::MinMax(MinMaxScaler {
+                low,
+                high,
+                min: cursor.f32s(n)?,
+                max: cursor.f32s(n)?,
+            })
+        }
+        3 => {
+            let (lower, upper) = (cursor.f32()?, cursor.f32()?);
+            let n = cursor.u32()? as usize;
+            Scaler::Robust(RobustScaler {
+                lower,
+                upper,
+                median: cursor.f32s(n)?,
+                iqr: cursor.f32s(n)?,
+            })
+        }
+        4 => Scaler::Normalizer(Normalizer::new(match cursor.u8()? {
+            0 => Norm::L1,
+            1 => Norm::L2,
+            2 => Norm::Max,
+            norm => return Err(Error::UnknownNorm(norm)),
+        })),
+        kind => return Err(Error::UnknownScaler(kind)),
+    }))
+}